@@ -20,3 +20,65 @@ fn bench_step4_recur(b: &mut Bencher) {
   b.iter(|| step4_recur(gen, 20));
 }
 */
+
+#[bench]
+fn bench_run_world_turn(b: &mut Bencher) {
+  let mut world = GameWorld::new(u64_from_time());
+  b.iter(|| world.run_world_turn(MOVE_COST));
+}
+
+/// The spot that's the longest walk from the player, for the path benches.
+fn far_away_goal(world: &GameWorld) -> Location {
+  let map = world.toward_player_map();
+  world
+    .terrain
+    .iter()
+    .filter_map(|(loc, _)| map.get(loc).map(|distance| (distance, loc.x, loc.y)))
+    .max()
+    .map(|(_, x, y)| Location { x, y })
+    .unwrap()
+}
+
+#[bench]
+fn bench_a_star_old(b: &mut Bencher) {
+  let world = GameWorld::new(1);
+  let goal = far_away_goal(&world);
+  let terrain = &world.terrain;
+  b.iter(|| a_star(world.player_location, goal, |loc| terrain.get(loc).is_walkable()));
+}
+
+#[bench]
+fn bench_find_path_four_way(b: &mut Bencher) {
+  let world = GameWorld::new(1);
+  let goal = far_away_goal(&world);
+  let terrain = &world.terrain;
+  b.iter(|| {
+    find_path(world.player_location, goal, PathOptions::default(), |loc| {
+      if terrain.get(loc).is_walkable() {
+        Some(1)
+      } else {
+        None
+      }
+    })
+  });
+}
+
+#[bench]
+fn bench_find_path_eight_way(b: &mut Bencher) {
+  let world = GameWorld::new(1);
+  let goal = far_away_goal(&world);
+  let terrain = &world.terrain;
+  let options = PathOptions {
+    movement: Movement::EightWay,
+    ..PathOptions::default()
+  };
+  b.iter(|| {
+    find_path(world.player_location, goal, options, |loc| {
+      if terrain.get(loc).is_walkable() {
+        Some(1)
+      } else {
+        None
+      }
+    })
+  });
+}
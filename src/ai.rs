@@ -0,0 +1,461 @@
+//! How monsters decide what to do with their turn.
+//!
+//! Each `AiKind` has a behavior that implements `Ai`. A behavior only gets to
+//! look at the world, it hands back a `MonsterAction` and the world carries
+//! that out. Tests can swap in their own `Ai` for any creature with
+//! `GameWorld::set_ai`, which only exists in test builds.
+
+use super::*;
+
+/// The most tiles that a monster looks at when it's finding a path.
+pub const PATH_SEARCH_BUDGET: usize = 2000;
+
+/// How close a kiter likes to be when it's shooting.
+pub const KITER_RANGE: i32 = 4;
+
+/// The farthest that a monster throws things.
+pub const THROW_RANGE: i32 = 6;
+
+/// How many of a pack (counting itself) need to be in sight before a pack
+/// hunter will go after the player.
+pub const PACK_SIZE: usize = 3;
+
+/// How many turns a monster keeps hunting for the player after losing sight
+/// of them, unless its def says otherwise.
+pub const DEFAULT_MEMORY_SPAN: u32 = 20;
+
+/// How far a monster strays from where it last saw the player while it
+/// searches for them.
+pub const SEARCH_RADIUS: i32 = 3;
+
+/// Where a monster last saw the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerMemory {
+  pub last_seen: Location,
+  /// How many of its own turns ago that was.
+  pub turns_since: u32,
+  /// Set once it's gotten to `last_seen` and started looking around.
+  pub searching: bool,
+}
+
+/// Something that a monster can do with its turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonsterAction {
+  /// Steps one tile over by the delta given, attacking anyone who's there.
+  Step(Location),
+  /// Attacks from afar, at whoever is first in the line to the location
+  /// given.
+  Shoot(Location),
+  /// Throws an item that it's carrying at the location given.
+  Throw(Item, Location),
+  /// Does nothing.
+  Wait,
+}
+
+/// What a monster knows about the world while it decides what to do.
+pub struct AiView<'a> {
+  pub world: &'a GameWorld,
+  pub me: &'a Creature,
+  pub location: Location,
+  /// Every location that it can see right now.
+  pub seen: &'a HashSet<Location>,
+}
+
+impl<'a> AiView<'a> {
+  /// Where the player is, if they're in sight.
+  pub fn player_location(&self) -> Option<Location> {
+    let player_location = self.world.player_location;
+    if self.world.creatures.player().is_some() && self.seen.contains(&player_location) {
+      Some(player_location)
+    } else {
+      None
+    }
+  }
+
+  /// The number of steps between here and there, ignoring walls.
+  pub fn distance_to(&self, there: Location) -> i32 {
+    steps_between(self.location, there)
+  }
+
+  /// If it's down to a third of its hit points or less.
+  pub fn is_badly_hurt(&self) -> bool {
+    self.me.hit_points * 3 <= self.me.max_hit_points
+  }
+
+  /// The first step along a path to the target, if there's a path that's
+  /// not too hard to find.
+  pub fn step_toward(&self, target: Location) -> Option<Location> {
+    let terrain_ref = &self.world.terrain;
+    let options = PathOptions {
+      movement: Movement::EightWay,
+      budget: PATH_SEARCH_BUDGET,
+    };
+    let path = find_path(self.location, target, options, |loc| if terrain_ref.get(loc).is_walkable() { Some(1) } else { None })?;
+    debug_assert_eq!(self.location, path[0]);
+    path.get(1).map(|&next| next - self.location)
+  }
+
+  /// The next step toward the player, using the map that every monster
+  /// shares.
+  pub fn chase_step(&self) -> Option<Location> {
+    self.world.toward_player_map().downhill(self.location, |_| true)
+  }
+
+  /// The next step in running away from the player, if there's an open one
+  /// that helps.
+  pub fn flee_step(&self) -> Option<Location> {
+    let creatures = &self.world.creatures;
+    self.world.away_from_player_map().downhill(self.location, |next| creatures.id_at(next).is_none())
+  }
+
+  /// A step in a random direction.
+  pub fn random_step(&self, gen: &mut PCG32) -> Location {
+    EIGHT_WAY_DELTAS[(gen.next_u32() >> 29) as usize]
+  }
+
+  /// Heads for where it last saw the player, then searches around there.
+  /// Gives `None` if it isn't looking for the player.
+  pub fn hunt(&self, gen: &mut PCG32) -> Option<MonsterAction> {
+    let last_seen = self.me.memory?.last_seen;
+    if self.me.memory?.searching {
+      let delta = self.random_step(gen);
+      let next = self.location + delta;
+      if steps_between(next, last_seen) <= SEARCH_RADIUS {
+        return Some(MonsterAction::Step(delta));
+      }
+    }
+    self.step_toward(last_seen).map(MonsterAction::Step)
+  }
+
+  /// Heads for a noise that it heard. Gives `None` if it didn't hear one (or
+  /// can't get there).
+  pub fn investigate(&self) -> Option<MonsterAction> {
+    self.me.investigating.and_then(|target| self.step_toward(target)).map(MonsterAction::Step)
+  }
+
+  /// If something fired from here at the target would get there without
+  /// hitting a wall or anyone else first.
+  pub fn has_clear_shot(&self, target: Location) -> bool {
+    self.world.trace_projectile(self.location, target).impact == target
+  }
+
+  /// A throw at the player, if it's carrying something to throw and has a
+  /// clear shot that won't catch it in the blast too.
+  pub fn throw_at_player(&self) -> Option<MonsterAction> {
+    let target = self.player_location()?;
+    let distance = self.distance_to(target);
+    if distance > THROW_RANGE || !self.has_clear_shot(target) {
+      return None;
+    }
+    let registry = &self.world.item_registry;
+    self
+      .me
+      .inventory
+      .iter()
+      .cloned()
+      .find(|&item| registry.def(item).needs_target && registry.def(item).blast_radius < distance)
+      .map(|item| MonsterAction::Throw(item, target))
+  }
+
+  /// Where every other creature of its own kind that it can see is.
+  pub fn kin_in_sight(&self) -> Vec<Location> {
+    self
+      .world
+      .creatures
+      .iter()
+      .filter(|&(creature, loc)| creature.id != self.me.id && creature.name == self.me.name && self.seen.contains(&loc))
+      .map(|(_, loc)| loc)
+      .collect()
+  }
+}
+
+/// Something that can decide what a creature does.
+pub trait Ai: ::std::fmt::Debug {
+  fn choose_action(&mut self, view: &AiView, gen: &mut PCG32) -> MonsterAction;
+}
+
+/// Goes after the player whenever it can see them (throwing anything it has
+/// at them on the way), and hunts for them for a while after losing them.
+/// Otherwise it wanders.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChaserAi;
+
+impl Ai for ChaserAi {
+  fn choose_action(&mut self, view: &AiView, gen: &mut PCG32) -> MonsterAction {
+    if let Some(throw) = view.throw_at_player() {
+      return throw;
+    }
+    match view.player_location().and_then(|_| view.chase_step()) {
+      Some(delta) => MonsterAction::Step(delta),
+      None => view.hunt(gen).or_else(|| view.investigate()).unwrap_or_else(|| WandererAi.choose_action(view, gen)),
+    }
+  }
+}
+
+/// Walks about at random.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WandererAi;
+
+impl Ai for WandererAi {
+  fn choose_action(&mut self, view: &AiView, gen: &mut PCG32) -> MonsterAction {
+    MonsterAction::Step(view.random_step(gen))
+  }
+}
+
+/// Fights like a chaser until it's badly hurt, then runs for it. If it's
+/// cornered it fights anyway.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CowardAi;
+
+impl Ai for CowardAi {
+  fn choose_action(&mut self, view: &AiView, gen: &mut PCG32) -> MonsterAction {
+    match view.player_location() {
+      Some(_) if view.is_badly_hurt() => match view.flee_step() {
+        Some(delta) => MonsterAction::Step(delta),
+        None => ChaserAi.choose_action(view, gen),
+      },
+      _ => ChaserAi.choose_action(view, gen),
+    }
+  }
+}
+
+/// Shoots at the player from a distance, backing off if they get close and
+/// closing in if they get too far (or if something is in the way).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KiterAi;
+
+impl Ai for KiterAi {
+  fn choose_action(&mut self, view: &AiView, gen: &mut PCG32) -> MonsterAction {
+    if let Some(throw) = view.throw_at_player() {
+      return throw;
+    }
+    match view.player_location() {
+      Some(target) => {
+        let distance = view.distance_to(target);
+        if distance > KITER_RANGE || !view.has_clear_shot(target) {
+          ChaserAi.choose_action(view, gen)
+        } else if distance > 1 {
+          MonsterAction::Shoot(target)
+        } else {
+          match view.flee_step() {
+            Some(delta) => MonsterAction::Step(delta),
+            None => MonsterAction::Shoot(target),
+          }
+        }
+      }
+      None => view.hunt(gen).or_else(|| view.investigate()).unwrap_or_else(|| WandererAi.choose_action(view, gen)),
+    }
+  }
+}
+
+/// Only goes after the player with enough of its pack in sight. Until then it
+/// heads for the rest of its pack.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PackAi;
+
+impl Ai for PackAi {
+  fn choose_action(&mut self, view: &AiView, gen: &mut PCG32) -> MonsterAction {
+    let kin = view.kin_in_sight();
+    match view.player_location() {
+      Some(target) if kin.len() + 1 >= PACK_SIZE || view.distance_to(target) == 1 => ChaserAi.choose_action(view, gen),
+      _ => {
+        let nearest_kin = kin.into_iter().min_by_key(|&loc| (view.distance_to(loc), loc.x, loc.y));
+        match nearest_kin.filter(|&loc| view.distance_to(loc) > 1).and_then(|loc| view.step_toward(loc)) {
+          Some(delta) => MonsterAction::Step(delta),
+          None => WandererAi.choose_action(view, gen),
+        }
+      }
+    }
+  }
+}
+
+impl AiKind {
+  /// The behavior that goes with this kind of AI.
+  pub fn behavior(self) -> Box<dyn Ai> {
+    match self {
+      AiKind::Chaser => Box::new(ChaserAi),
+      AiKind::Wanderer => Box::new(WandererAi),
+      AiKind::Coward => Box::new(CowardAi),
+      AiKind::Kiter => Box::new(KiterAi),
+      AiKind::Pack => Box::new(PackAi),
+    }
+  }
+}
+
+impl GameWorld {
+  /// Makes a creature use the AI given instead of its own, for scripting
+  /// monsters in tests.
+  #[cfg(test)]
+  pub(crate) fn set_ai(&mut self, creature_id: CreatureID, ai: Box<dyn Ai>) {
+    self.ai_overrides.insert(creature_id, ai);
+  }
+
+  /// Updates what a monster remembers about the player, given what it can see
+  /// right now. Seeing the player, or getting to where a noise was, means
+  /// there's no noise left to investigate.
+  fn update_memory(&mut self, creature_id: CreatureID, seen: &HashSet<Location>) {
+    let player_location = self.player_location;
+    let player_seen = self.creatures.player().is_some() && seen.contains(&player_location);
+    let location = self.creatures.location_of(creature_id).unwrap();
+    let creature_mut = self.creatures.get_mut(creature_id).unwrap();
+    if player_seen || creature_mut.investigating == Some(location) {
+      creature_mut.investigating = None;
+    }
+    creature_mut.memory = if player_seen {
+      Some(PlayerMemory {
+        last_seen: player_location,
+        turns_since: 0,
+        searching: false,
+      })
+    } else {
+      match creature_mut.memory {
+        Some(mut memory) if memory.turns_since < creature_mut.memory_span => {
+          memory.turns_since += 1;
+          memory.searching |= location == memory.last_seen;
+          Some(memory)
+        }
+        _ => None,
+      }
+    };
+  }
+
+  /// Asks a monster's AI what it wants to do.
+  pub(crate) fn choose_monster_action(&mut self, creature_id: CreatureID, seen: &HashSet<Location>) -> MonsterAction {
+    self.update_memory(creature_id, seen);
+    let mut ai = self.creatures.get(creature_id).unwrap().ai.behavior();
+    // a scripted AI from `set_ai` gets taken out while it chooses, then put
+    // back after.
+    #[cfg(test)]
+    let scripted = match self.ai_overrides.remove(&creature_id) {
+      Some(scripted) => {
+        ai = scripted;
+        true
+      }
+      None => false,
+    };
+    let mut gen = self.gen.clone();
+    let action = {
+      let view = AiView {
+        world: self,
+        me: self.creatures.get(creature_id).unwrap(),
+        location: self.creatures.location_of(creature_id).unwrap(),
+        seen,
+      };
+      ai.choose_action(&view, &mut gen)
+    };
+    self.gen = gen;
+    #[cfg(test)]
+    {
+      if scripted {
+        self.ai_overrides.insert(creature_id, ai);
+      }
+    }
+    action
+  }
+}
+
+/// Does whatever it's told, in order, then waits.
+#[cfg(test)]
+#[derive(Debug)]
+struct ScriptedAi(Vec<MonsterAction>);
+
+#[cfg(test)]
+impl Ai for ScriptedAi {
+  fn choose_action(&mut self, _view: &AiView, _gen: &mut PCG32) -> MonsterAction {
+    if self.0.is_empty() {
+      MonsterAction::Wait
+    } else {
+      self.0.remove(0)
+    }
+  }
+}
+
+#[test]
+fn test_scripted_ai() {
+  let mut world = GameWorld::test_arena(99);
+  let spot = world.player_location + Location { x: 2, y: 0 };
+  let monster_id = world.spawn_named_at("kestrel", spot);
+  let west = Location { x: -1, y: 0 };
+  world.set_ai(
+    monster_id,
+    Box::new(ScriptedAi(vec![MonsterAction::Step(west), MonsterAction::Step(west)])),
+  );
+  world.messages.clear();
+
+  world.run_world_turn(WAIT_COST);
+  assert_eq!(world.creatures.location_of(monster_id), Some(spot + west));
+  assert!(world.messages.is_empty());
+  world.run_world_turn(WAIT_COST);
+  assert_eq!(world.creatures.location_of(monster_id), Some(spot + west));
+  let first_event = world.messages.iter().next().cloned();
+  match first_event {
+    Some(GameEvent::Attack { attacker, target, .. }) => assert!(attacker.id == monster_id && target.is_the_player),
+    other => panic!("expected an attack, got {:?}", other),
+  }
+}
+
+#[test]
+fn test_cowards_run_when_hurt() {
+  let mut world = GameWorld::test_arena(100);
+  let spot = world.player_location + Location { x: 1, y: 0 };
+  let monster_id = world.spawn_named_at("rat", spot);
+  world.creatures.get_mut(monster_id).unwrap().hit_points = 1;
+  world.run_world_turn(WAIT_COST);
+  let new_spot = world.creatures.location_of(monster_id).unwrap();
+  assert_ne!(new_spot, spot);
+  assert!(world.away_from_player_map().get(new_spot) < world.away_from_player_map().get(spot));
+  assert_eq!(world.creatures.player().unwrap().hit_points, 20);
+}
+
+#[test]
+fn test_monsters_hunt_where_they_last_saw_the_player() {
+  let mut world = GameWorld::test_arena(101);
+  let spot = world.player_location + Location { x: 3, y: 0 };
+  let last_seen = world.player_location + Location { x: 3, y: -2 };
+  let monster_id = world.spawn_named_at("kestrel", spot);
+  {
+    let monster_mut = world.creatures.get_mut(monster_id).unwrap();
+    // blind, so that it has to go on memory alone
+    monster_mut.sight = 0;
+    monster_mut.memory_span = 3;
+    monster_mut.memory = Some(PlayerMemory {
+      last_seen,
+      turns_since: 0,
+      searching: false,
+    });
+  }
+  world.run_world_turn(WAIT_COST);
+  world.run_world_turn(WAIT_COST);
+  assert_eq!(world.creatures.location_of(monster_id), Some(last_seen));
+  world.run_world_turn(WAIT_COST);
+  let memory = world.creatures.get(monster_id).unwrap().memory.unwrap();
+  assert!(memory.searching);
+  assert_eq!(memory.turns_since, 3);
+  world.run_world_turn(WAIT_COST);
+  assert_eq!(world.creatures.get(monster_id).unwrap().memory, None);
+}
+
+#[test]
+fn test_monsters_throw_what_they_carry() {
+  let mut world = GameWorld::test_arena(102);
+  let poison_bomb = world.item_registry.by_name("Poison Bomb").unwrap();
+  let spot = world.player_location + Location { x: 3, y: 0 };
+  let monster_id = world
+    .creatures
+    .spawn(spot, |id| {
+      let mut out = Creature::new(id, b'j', 0);
+      out.inventory.push(poison_bomb);
+      out
+    })
+    .unwrap();
+  world.run_world_turn(WAIT_COST);
+  assert!(world.creatures.get(monster_id).unwrap().inventory.is_empty());
+  assert!(world.creatures.player().unwrap().has_status(StatusKind::Poisoned));
+
+  // with someone in the way, it would only hit them, so it doesn't throw
+  let blocker_spot = world.player_location + Location { x: 1, y: 0 };
+  world.creatures.spawn(blocker_spot, |id| Creature::new(id, b'r', 0)).unwrap();
+  world.creatures.get_mut(monster_id).unwrap().inventory.push(poison_bomb);
+  world.run_world_turn(WAIT_COST);
+  assert_eq!(world.creatures.get(monster_id).unwrap().inventory, vec![poison_bomb]);
+}
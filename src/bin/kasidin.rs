@@ -10,33 +10,39 @@ extern crate roguelike_tutorial_2018;
 use roguelike_tutorial_2018::*;
 
 // std
-use std::collections::btree_map::*;
-use std::collections::hash_set::*;
+use std::fs::File;
 use std::io::*;
 
 const TILE_GRID_WIDTH: usize = 66;
 const TILE_GRID_HEIGHT: usize = 50;
-const KINDA_LIME_GREEN: u32 = rgb32!(128, 255, 20);
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum DisplayMode {
-  Game,
-  Inventory,
-  ItemTargeting(char, Location),
-}
+const SAVE_FILE_NAME: &str = "kasidin.sav";
+const REPLAY_FILE_NAME: &str = "kasidin.replay";
+const MORGUE_FILE_NAME: &str = "kasidin-morgue.txt";
 
 fn main() {
   let mut term = unsafe { DwarfTerm::new(TILE_GRID_WIDTH, TILE_GRID_HEIGHT, "Kasidin").expect("WHOOPS!") };
   term.set_all_foregrounds(KINDA_LIME_GREEN);
   term.set_all_backgrounds(0);
 
-  let mut game = GameWorld::new(u64_from_time());
+  // A resumed game can't be played back from its seed, so we only keep an
+  // action log for games that were started fresh.
+  let mut driver = match resume_saved_game() {
+    Some(game) => GameDriver::new(game, None, TILE_GRID_WIDTH, TILE_GRID_HEIGHT),
+    None => {
+      let (log, game) = ActionLog::new_game(u64_from_time());
+      GameDriver::new(game, Some(log), TILE_GRID_WIDTH, TILE_GRID_HEIGHT)
+    }
+  };
+  let mut screen = Screen::new(TILE_GRID_WIDTH, TILE_GRID_HEIGHT);
+  let (keymap, note) = Keymap::load_or_create(KEYMAP_FILE_NAME);
+  if let Some(note) = note {
+    eprintln!("{}", note);
+  }
 
   // Main loop
   let mut running = true;
+  let mut morgue_written = driver.game.game_over().is_some();
   let mut pending_keys = vec![];
-  let mut display_mode = DisplayMode::Game;
-  let mut seen_set = HashSet::new();
   'game: loop {
     // Grab all new presses
     term.poll_events(|event| match event {
@@ -59,86 +65,31 @@ fn main() {
       _ => {}
     });
     if !running {
+      // once you're dead there's nothing to come back to
+      if driver.game.game_over().is_none() {
+        save_game(&driver.game);
+      }
+      if let Some(log) = driver.action_log.as_ref() {
+        save_action_log(log);
+      }
       break 'game;
     }
 
     for key in pending_keys.drain(..) {
-      match display_mode {
-        DisplayMode::Game => match key {
-          VirtualKeyCode::Up => game.move_player(Location { x: 0, y: 1 }),
-          VirtualKeyCode::Down => game.move_player(Location { x: 0, y: -1 }),
-          VirtualKeyCode::Left => game.move_player(Location { x: -1, y: 0 }),
-          VirtualKeyCode::Right => game.move_player(Location { x: 1, y: 0 }),
-          VirtualKeyCode::I => display_mode = DisplayMode::Inventory,
-          _ => {}
-        },
-        DisplayMode::Inventory => match key {
-          VirtualKeyCode::Escape => display_mode = DisplayMode::Game,
-          other => {
-            letter_of(other).map(|ch| {
-              if ch.is_alphabetic() {
-                match game.use_item(ch) {
-                  UseItemResult::NoSuchItem => {}
-                  UseItemResult::ItemUsed => {
-                    display_mode = DisplayMode::Game;
-                  }
-                  UseItemResult::ItemNeedsTarget => {
-                    display_mode = DisplayMode::ItemTargeting(ch, Location { x: 0, y: 0 });
-                  }
-                }
-              }
-            });
-          }
-        },
-        DisplayMode::ItemTargeting(letter, delta) => match key {
-          VirtualKeyCode::Escape => display_mode = DisplayMode::Game,
-          VirtualKeyCode::Return => {
-            game.use_targeted_item(letter, delta);
-            display_mode = DisplayMode::Game;
-          }
-          VirtualKeyCode::Up | VirtualKeyCode::Down | VirtualKeyCode::Left | VirtualKeyCode::Right => {
-            let delta_change = match key {
-              VirtualKeyCode::Up => Location { x: 0, y: 1 },
-              VirtualKeyCode::Down => Location { x: 0, y: -1 },
-              VirtualKeyCode::Left => Location { x: -1, y: 0 },
-              VirtualKeyCode::Right => Location { x: 1, y: 0 },
-              _ => unreachable!(),
-            };
-            let new_delta = delta + delta_change;
-            if seen_set.contains(&(game.player_location + new_delta)) {
-              display_mode = DisplayMode::ItemTargeting(letter, new_delta);
-            }
-          }
-          _ => {}
-        },
+      match keymap.command_for(driver.display_mode, &format!("{:?}", key)) {
+        Some(Command::Quit) => running = false,
+        Some(command) => driver.handle(command),
+        None => {}
       }
     }
-    // assumes that the display is wider than tall
-    const FOV_DISPLAY_RANGE: i32 = TILE_GRID_WIDTH as i32 / 2;
-    // TODO: we should actually only adjust the seen set if the player moved. We
-    // should probably make this part of the GameWorld so that it can refresh it
-    // when necessary and then we just read that.
-    seen_set.clear();
-    ppfov(
-      (game.player_location.x, game.player_location.y),
-      FOV_DISPLAY_RANGE,
-      |x, y| {
-        game
-          .terrain
-          .get(&Location { x, y })
-          .map(|&t| t == Terrain::Wall || t == Terrain::Ice)
-          .unwrap_or(true)
-      },
-      |x, y| drop(seen_set.insert(Location { x, y })),
-    );
-    {
-      match display_mode {
-        DisplayMode::Game => draw_game(&mut term, &game, &seen_set),
-        DisplayMode::Inventory => draw_inventory(&mut term, &game),
-        DisplayMode::ItemTargeting(_letter, delta) => draw_targeting(&mut term, &game, &seen_set, delta),
-      }
+    if driver.game.game_over().is_some() && !morgue_written {
+      write_morgue(&driver.game);
+      morgue_written = true;
     }
 
+    driver.draw(&mut screen);
+    copy_screen_to_term(&screen, &mut term);
+
     unsafe {
       term
         .clear_draw_swap()
@@ -152,239 +103,74 @@ fn main() {
   }
 }
 
-fn letter_of(keycode: VirtualKeyCode) -> Option<char> {
-  match keycode {
-    VirtualKeyCode::A => Some('a'),
-    VirtualKeyCode::B => Some('b'),
-    VirtualKeyCode::C => Some('c'),
-    VirtualKeyCode::D => Some('d'),
-    VirtualKeyCode::E => Some('e'),
-    VirtualKeyCode::F => Some('f'),
-    VirtualKeyCode::G => Some('g'),
-    VirtualKeyCode::H => Some('h'),
-    VirtualKeyCode::I => Some('i'),
-    VirtualKeyCode::J => Some('j'),
-    VirtualKeyCode::K => Some('k'),
-    VirtualKeyCode::L => Some('l'),
-    VirtualKeyCode::M => Some('m'),
-    VirtualKeyCode::N => Some('n'),
-    VirtualKeyCode::O => Some('o'),
-    VirtualKeyCode::P => Some('p'),
-    VirtualKeyCode::Q => Some('q'),
-    VirtualKeyCode::R => Some('r'),
-    VirtualKeyCode::S => Some('s'),
-    VirtualKeyCode::T => Some('t'),
-    VirtualKeyCode::U => Some('u'),
-    VirtualKeyCode::V => Some('v'),
-    VirtualKeyCode::W => Some('w'),
-    VirtualKeyCode::X => Some('x'),
-    VirtualKeyCode::Y => Some('y'),
-    VirtualKeyCode::Z => Some('z'),
-    _ => None,
-  }
-}
-
-fn draw_game(term: &mut DwarfTerm, game: &GameWorld, seen_set: &HashSet<Location>) {
+fn copy_screen_to_term(screen: &Screen, term: &mut DwarfTerm) {
   let (mut fgs, mut bgs, mut ids) = term.layer_slices_mut();
-  // clear the display
-  fgs.set_all(rgb32!(255, 255, 255));
-  bgs.set_all(rgb32!(0, 0, 0));
-  ids.set_all(0);
-
-  let offset = game.player_location - Location {
-    x: (fgs.width() / 2) as i32,
-    y: (fgs.height() / 2) as i32,
-  };
-  // draw the map, save space for the status line.
-  const STATUS_HEIGHT: usize = 1;
-  let full_extent = (ids.width(), ids.height());
-  let map_view_end = (full_extent.0, full_extent.1 - STATUS_HEIGHT);
-  for (scr_x, scr_y, id_mut) in ids.slice_mut((0, 0)..map_view_end).iter_mut() {
-    let loc_for_this_screen_position = Location {
-      x: scr_x as i32,
-      y: scr_y as i32,
-    } + offset;
-    let (glyph, color) = if seen_set.contains(&loc_for_this_screen_position) {
-      match game.creature_locations.get(&loc_for_this_screen_position) {
-        Some(cid_ref) => {
-          let creature_here = game
-            .creature_list
-            .iter()
-            .find(|&creature_ref| &creature_ref.id == cid_ref)
-            .expect("Our locations and list are out of sync!");
-          (creature_here.icon, creature_here.color)
-        }
-        None => match game
-          .item_locations
-          .get(&loc_for_this_screen_position)
-          .and_then(|item_vec_ref| item_vec_ref.get(0))
-        {
-          Some(Item::PotionHealth) => (POTION_GLYPH, rgb32!(250, 5, 5)),
-          Some(Item::PotionStrength) => (POTION_GLYPH, rgb32!(5, 240, 20)),
-          Some(Item::BombBlast) => (BOMB_GLYPH, rgb32!(127, 127, 127)),
-          Some(Item::BombIce) => (BOMB_GLYPH, rgb32!(153, 217, 234)),
-          None => match game.terrain.get(&loc_for_this_screen_position) {
-            Some(Terrain::Wall) => (WALL_TILE, rgb32!(155, 75, 0)),
-            Some(Terrain::Ice) => (WALL_TILE, rgb32!(112, 146, 190)),
-            Some(Terrain::Floor) => (b'.', rgb32!(128, 128, 128)),
-            None => (b' ', 0),
-          },
-        },
-      }
-    } else {
-      (b' ', 0)
-    };
-    *id_mut = glyph;
-    fgs[(scr_x, scr_y)] = color;
+  for (x, y, fg_mut) in fgs.iter_mut() {
+    *fg_mut = screen.fgs[(x, y)];
   }
-  // draw the status bar.
-  fgs.slice_mut((0, map_view_end.1)..full_extent).set_all(KINDA_LIME_GREEN);
-  bgs.slice_mut((0, map_view_end.1)..full_extent).set_all(rgb32!(0, 0, 0));
-  let mut ids_status_slice_mut = ids.slice_mut((0, map_view_end.1)..full_extent);
-  debug_assert_eq!(ids_status_slice_mut.width(), full_extent.0);
-  debug_assert_eq!(ids_status_slice_mut.height(), STATUS_HEIGHT);
-  ids_status_slice_mut.set_all(0);
-  debug_assert_eq!(1, STATUS_HEIGHT);
-  let mut status_line_u8_slice_mut: &mut [u8] = unsafe { ::std::slice::from_raw_parts_mut(ids_status_slice_mut.as_mut_ptr(), full_extent.0) };
-  let player_hp = game
-    .creature_list
-    .iter()
-    .find(|creature_ref| creature_ref.is_the_player)
-    .unwrap()
-    .hit_points;
-  write!(status_line_u8_slice_mut, "HP: {}, Enemies: {}", player_hp, game.creature_list.len() - 1).ok();
-}
-
-fn draw_inventory(term: &mut DwarfTerm, game: &GameWorld) {
-  let (mut fgs, mut bgs, mut ids) = term.layer_slices_mut();
-  // clear the display
-  fgs.set_all(rgb32!(255, 255, 255));
-  bgs.set_all(rgb32!(0, 0, 0));
-  ids.set_all(0);
-
-  let mut map_item_count = BTreeMap::new();
-  for item_ref in game
-    .creature_list
-    .iter()
-    .find(|creature_ref| creature_ref.is_the_player)
-    .unwrap()
-    .inventory
-    .iter()
-  {
-    *map_item_count.entry(item_ref).or_insert(0) += 1;
+  for (x, y, bg_mut) in bgs.iter_mut() {
+    *bg_mut = screen.bgs[(x, y)];
   }
+  for (x, y, id_mut) in ids.iter_mut() {
+    *id_mut = screen.ids[(x, y)];
+  }
+}
 
-  let mut item_list = vec![];
-  for (key, val) in map_item_count.into_iter() {
-    match val {
-      0 => panic!("what the heck?"),
-      1 => item_list.push(format!("{}", key)),
-      count => item_list.push(format!("{} ({})", key, count)),
+/// Writes out the action log so that the session can be replayed later.
+fn save_action_log(log: &ActionLog) {
+  match File::create(REPLAY_FILE_NAME) {
+    Ok(file) => {
+      log
+        .write_to(BufWriter::new(file))
+        .map_err(|e| eprintln!("couldn't write the action log: {}", e))
+        .ok();
     }
+    Err(e) => eprintln!("couldn't create the action log: {}", e),
   }
+}
 
-  // draw the menu title
-  {
-    let menu_title = "== Inventory ==";
-    assert!(menu_title.len() < ids.width());
-    let x_offset = (ids.width() - menu_title.len()) as isize / 2;
-    let y_offset = (ids.height() as isize - 1) as isize;
-    let mut this_line_slice_mut: &mut [u8] =
-      unsafe { ::std::slice::from_raw_parts_mut(ids.as_mut_ptr().offset(x_offset + y_offset * ids.pitch()), menu_title.len()) };
-    write!(this_line_slice_mut, "{}", menu_title).ok();
-  }
-  // draw the items
-  if item_list.len() > 0 {
-    let mut the_y_position: isize = ids.height() as isize - 2;
-    for (i, item) in item_list.into_iter().enumerate() {
-      if the_y_position < 0 {
-        break;
-      }
-      let mut this_line_slice_mut: &mut [u8] =
-        unsafe { ::std::slice::from_raw_parts_mut(ids.as_mut_ptr().offset(ids.pitch() * the_y_position), ids.width()) };
-      let letter = i + ('a' as u8 as usize);
-      write!(this_line_slice_mut, "{}) {}", letter as u8 as char, item).ok();
-      the_y_position -= 1;
+/// Writes the morgue file for a game that just ended.
+fn write_morgue(game: &GameWorld) {
+  match File::create(MORGUE_FILE_NAME) {
+    Ok(file) => {
+      game
+        .write_morgue(BufWriter::new(file))
+        .map_err(|e| eprintln!("couldn't write the morgue file: {}", e))
+        .ok();
     }
-  } else {
-    let message = "You have no items on hand.";
-    assert!(message.len() < ids.width());
-    let x_offset = (ids.width() - message.len()) as isize / 2;
-    let y_offset = (ids.height() as isize - 3) as isize;
-    let mut this_line_slice_mut: &mut [u8] =
-      unsafe { ::std::slice::from_raw_parts_mut(ids.as_mut_ptr().offset(x_offset + y_offset * ids.pitch()), message.len()) };
-    write!(this_line_slice_mut, "{}", message).ok();
+    Err(e) => eprintln!("couldn't create the morgue file: {}", e),
   }
 }
 
-fn draw_targeting(term: &mut DwarfTerm, game: &GameWorld, seen_set: &HashSet<Location>, delta: Location) {
-  let (mut fgs, mut bgs, mut ids) = term.layer_slices_mut();
-  // clear the display
-  fgs.set_all(rgb32!(255, 255, 255));
-  bgs.set_all(rgb32!(0, 0, 0));
-  ids.set_all(0);
-
-  // draw the menu title
-  {
-    let menu_title = "== Select A Target ==";
-    assert!(menu_title.len() < ids.width());
-    let x_offset = (ids.width() - menu_title.len()) as isize / 2;
-    let y_offset = (ids.height() as isize - 1) as isize;
-    let mut this_line_slice_mut: &mut [u8] =
-      unsafe { ::std::slice::from_raw_parts_mut(ids.as_mut_ptr().offset(x_offset + y_offset * ids.pitch()), menu_title.len()) };
-    write!(this_line_slice_mut, "{}", menu_title).ok();
+/// Loads the saved game, if there is one.
+///
+/// The save file is deleted once it's been loaded, so you can't just keep
+/// reloading the same save over and over.
+fn resume_saved_game() -> Option<GameWorld> {
+  let file = File::open(SAVE_FILE_NAME).ok()?;
+  match GameWorld::load_from(BufReader::new(file)) {
+    Ok(game) => {
+      ::std::fs::remove_file(SAVE_FILE_NAME)
+        .map_err(|e| eprintln!("couldn't remove the save file: {}", e))
+        .ok();
+      Some(game)
+    }
+    Err(e) => {
+      eprintln!("couldn't load the save file, starting a new game: {}", e);
+      None
+    }
   }
+}
 
-  let offset = game.player_location - Location {
-    x: (fgs.width() / 2) as i32,
-    y: (fgs.height() / 2) as i32,
-  };
-  let target_delta_location = game.player_location + delta;
-  // draw the map, save space for the status line.
-  const STATUS_HEIGHT: usize = 1;
-  let full_extent = (ids.width(), ids.height());
-  let map_view_end = (full_extent.0, full_extent.1 - STATUS_HEIGHT);
-  for (scr_x, scr_y, id_mut) in ids.slice_mut((0, 0)..map_view_end).iter_mut() {
-    let loc_for_this_screen_position = Location {
-      x: scr_x as i32,
-      y: scr_y as i32,
-    } + offset;
-    let (glyph, color) = if seen_set.contains(&loc_for_this_screen_position) {
-      match game.creature_locations.get(&loc_for_this_screen_position) {
-        Some(cid_ref) => {
-          let creature_here = game
-            .creature_list
-            .iter()
-            .find(|&creature_ref| &creature_ref.id == cid_ref)
-            .expect("Our locations and list are out of sync!");
-          (creature_here.icon, creature_here.color)
-        }
-        None => match game
-          .item_locations
-          .get(&loc_for_this_screen_position)
-          .and_then(|item_vec_ref| item_vec_ref.get(0))
-        {
-          Some(Item::PotionHealth) => (POTION_GLYPH, rgb32!(250, 5, 5)),
-          Some(Item::PotionStrength) => (POTION_GLYPH, rgb32!(5, 240, 20)),
-          Some(Item::BombBlast) => (BOMB_GLYPH, rgb32!(127, 127, 127)),
-          Some(Item::BombIce) => (BOMB_GLYPH, rgb32!(153, 217, 234)),
-          None => match game.terrain.get(&loc_for_this_screen_position) {
-            Some(Terrain::Wall) => (WALL_TILE, rgb32!(155, 75, 0)),
-            Some(Terrain::Ice) => (WALL_TILE, rgb32!(112, 146, 190)),
-            Some(Terrain::Floor) => (b'.', rgb32!(128, 128, 128)),
-            None => (b' ', 0),
-          },
-        },
-      }
-    } else {
-      (b' ', 0)
-    };
-    *id_mut = glyph;
-    fgs[(scr_x, scr_y)] = color;
-    if loc_for_this_screen_position == target_delta_location {
-      const FULL_ALPHA: u32 = rgba32!(0, 0, 0, 255);
-      fgs[(scr_x, scr_y)] = !fgs[(scr_x, scr_y)] | FULL_ALPHA;
-      bgs[(scr_x, scr_y)] = !bgs[(scr_x, scr_y)] | FULL_ALPHA;
+/// Writes the game out to the save file.
+fn save_game(game: &GameWorld) {
+  match File::create(SAVE_FILE_NAME) {
+    Ok(file) => {
+      game
+        .save_to(BufWriter::new(file))
+        .map_err(|e| eprintln!("couldn't write the save file: {}", e))
+        .ok();
     }
+    Err(e) => eprintln!("couldn't create the save file: {}", e),
   }
 }
@@ -0,0 +1,119 @@
+//! A version of the game that doesn't need a window.
+//!
+//! Keys are read from stdin and the screen is printed to stdout as text
+//! after each line of input. This is mostly so that games can be played (and
+//! scripted) on machines without a GPU.
+//!
+//! The keys do whatever the key bindings say, the same as in the windowed
+//! game, from `kasidin-keys.txt` (which gets the defaults from
+//! `data/keys.txt` written into it if it isn't there yet). Each character of
+//! a line is one key:
+//!
+//! * `a` to `z` (either case) are those letters, and `0` to `9` are `key0` to
+//!   `key9`
+//! * `.` is `period`, `,` is `comma`, space is `space`, and Escape is
+//!   `escape`
+//!
+//! A line that starts with `:` names its keys instead, split up by spaces,
+//! for keys that aren't characters: `:return`, `:pageup numpad5`, and so on.
+//!
+//! Options: `--seed <number>` to pick the world, `--size <width>x<height>` to
+//! pick the screen size, and `--ansi` to print with terminal colors.
+
+extern crate roguelike_tutorial_2018;
+use roguelike_tutorial_2018::*;
+
+// std
+use std::io::*;
+
+const DEFAULT_WIDTH: usize = 66;
+const DEFAULT_HEIGHT: usize = 50;
+
+fn main() {
+  let mut seed = u64_from_time();
+  let mut ansi = false;
+  let mut size = (DEFAULT_WIDTH, DEFAULT_HEIGHT);
+  let mut args = ::std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    match arg.as_ref() {
+      "--ansi" => ansi = true,
+      "--seed" => {
+        seed = args
+          .next()
+          .and_then(|s| s.parse().ok())
+          .unwrap_or_else(|| usage_exit("--seed needs a number"))
+      }
+      "--size" => {
+        size = args
+          .next()
+          .and_then(|s| {
+            let mut parts = s.split('x').map(|part| part.parse::<usize>().ok());
+            match (parts.next(), parts.next(), parts.next()) {
+              (Some(Some(w)), Some(Some(h)), None) if w > 20 && h > 3 => Some((w, h)),
+              _ => None,
+            }
+          })
+          .unwrap_or_else(|| usage_exit("--size needs a size like 66x50"))
+      }
+      other => usage_exit(&format!("unknown argument: {}", other)),
+    }
+  }
+
+  let (log, game) = ActionLog::new_game(seed);
+  let mut driver = GameDriver::new(game, Some(log), size.0, size.1);
+  let mut screen = Screen::new(size.0, size.1);
+  let (keymap, note) = Keymap::load_or_create(KEYMAP_FILE_NAME);
+  if let Some(note) = note {
+    eprintln!("{}", note);
+  }
+
+  let stdin = stdin();
+  let stdout = stdout();
+  let mut out = stdout.lock();
+  driver.draw(&mut screen);
+  out.write_all(screen.to_text(ansi).as_bytes()).ok();
+  for line_result in stdin.lock().lines() {
+    let line = match line_result {
+      Ok(line) => line,
+      Err(e) => {
+        eprintln!("couldn't read stdin: {}", e);
+        break;
+      }
+    };
+    let key_names: Vec<String> = if line.starts_with(':') {
+      line[1..].split_whitespace().map(|name| name.to_string()).collect()
+    } else {
+      line.chars().filter_map(key_name).collect()
+    };
+    for name in key_names.iter() {
+      match keymap.command_for(driver.display_mode, name) {
+        Some(Command::Quit) => return,
+        Some(command) => driver.handle(command),
+        None => {}
+      }
+    }
+    driver.draw(&mut screen);
+    if out.write_all(screen.to_text(ansi).as_bytes()).is_err() {
+      break;
+    }
+  }
+}
+
+fn usage_exit(message: &str) -> ! {
+  eprintln!("{}", message);
+  eprintln!("usage: kasidin_headless [--seed <number>] [--size <width>x<height>] [--ansi]");
+  ::std::process::exit(1)
+}
+
+/// The name of the key that a character stands for, if it stands for one.
+fn key_name(ch: char) -> Option<String> {
+  match ch {
+    'a'..='z' | 'A'..='Z' => Some(ch.to_string()),
+    '0'..='9' => Some(format!("key{}", ch)),
+    '.' => Some("period".to_string()),
+    ',' => Some("comma".to_string()),
+    ' ' => Some("space".to_string()),
+    '\x1b' => Some("escape".to_string()),
+    _ => None,
+  }
+}
@@ -0,0 +1,104 @@
+//! Working out what happens when one creature attacks another.
+//!
+//! The attacker rolls its attack step, and has to roll at least the target's
+//! defense to hit at all. A hit rolls the attacker's damage step, and the
+//! target's armor gets taken off of that. If any of the attack dice exploded
+//! it's a critical hit, which goes right past the armor.
+
+use super::*;
+
+/// Everything about how one attack went.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttackResult {
+  /// What the attack step rolled.
+  pub attack_roll: i32,
+  /// What the attack roll had to match to hit.
+  pub defense: i32,
+  pub hit: bool,
+  /// The attack roll exploded, so the target's armor didn't count.
+  pub critical: bool,
+  /// What the damage step rolled, 0 for a miss.
+  pub damage_roll: i32,
+  /// How much armor got taken off of the damage roll.
+  pub armor: i32,
+  /// How much damage got through in the end.
+  pub damage: i32,
+}
+
+/// Rolls one attack. This doesn't change either creature.
+pub fn resolve_attack(gen: &mut PCG32, attacker: &Creature, target: &Creature) -> AttackResult {
+  let attack = step_roll(gen, attacker.attack_step);
+  let mut out = AttackResult {
+    attack_roll: attack.total,
+    defense: target.defense,
+    hit: attack.total >= target.defense,
+    critical: false,
+    damage_roll: 0,
+    armor: 0,
+    damage: 0,
+  };
+  if out.hit {
+    out.critical = attack.exploded;
+    out.damage_roll = step(gen, attacker.damage_step);
+    out.armor = if out.critical { 0 } else { target.armor };
+    out.damage = (out.damage_roll - out.armor).max(0);
+  }
+  out
+}
+
+impl GameWorld {
+  /// Has one creature attack another, hurting the target and logging how it
+  /// went. Fights are loud, so this makes a noise too.
+  pub(crate) fn perform_attack(&mut self, attacker_id: CreatureID, target_id: CreatureID, from_afar: bool) -> AttackResult {
+    let (result, attacker) = {
+      let attacker_ref = self.creatures.get(attacker_id).unwrap();
+      let target_ref = self.creatures.get(target_id).unwrap();
+      (resolve_attack(&mut self.gen, attacker_ref, target_ref), Who::of(attacker_ref))
+    };
+    let target_mut = self.creatures.get_mut(target_id).unwrap();
+    target_mut.hit_points -= result.damage;
+    if result.damage > 0 {
+      target_mut.hurt_by_player = attacker.is_the_player;
+    }
+    let target = Who::of(target_mut);
+    self.messages.push(if from_afar {
+      GameEvent::Shot { attacker, target, result }
+    } else {
+      GameEvent::Attack { attacker, target, result }
+    });
+    let target_location = self.creatures.location_of(target_id).unwrap();
+    self.make_noise(target_location, COMBAT_LOUDNESS, NoiseKind::Combat);
+    result
+  }
+}
+
+#[test]
+fn test_resolve_attack() {
+  let mut store = CreatureStore::new();
+  let attacker_id = store.spawn(Location { x: 0, y: 0 }, Creature::new_player).unwrap();
+  let target_id = store.spawn(Location { x: 1, y: 0 }, |id| Creature::new(id, b'k', 0)).unwrap();
+  let mut gen = PCG32::new(31);
+
+  // nothing gets past a high enough defense
+  store.get_mut(target_id).unwrap().defense = 1000;
+  for _ in 0..100 {
+    let result = resolve_attack(&mut gen, store.get(attacker_id).unwrap(), store.get(target_id).unwrap());
+    assert!(!result.hit && result.damage == 0);
+  }
+
+  // with lots of armor only critical hits do any damage
+  store.get_mut(target_id).unwrap().defense = 0;
+  store.get_mut(target_id).unwrap().armor = 1000;
+  let mut criticals = 0;
+  for _ in 0..1000 {
+    let result = resolve_attack(&mut gen, store.get(attacker_id).unwrap(), store.get(target_id).unwrap());
+    assert!(result.hit);
+    if result.critical {
+      criticals += 1;
+      assert_eq!(result.damage, result.damage_roll);
+    } else {
+      assert_eq!(result.damage, 0);
+    }
+  }
+  assert!(criticals > 0 && criticals < 500, "{} criticals", criticals);
+}
@@ -0,0 +1,277 @@
+//! Storage for all the creatures in a world.
+//!
+//! Creatures live in slots indexed by their `CreatureID`, and the store also
+//! keeps both directions of the creature/location mapping, so every lookup is
+//! O(1) no matter how many monsters there are. All changes go through the
+//! store's methods, which keep the indexes in sync.
+
+use super::*;
+
+/// Names a single creature within a single `GameWorld`.
+///
+/// The `index` can be reused once a creature is gone, but each reuse gets a
+/// new `generation`, so an old ID that someone held on to will never compare
+/// equal to the ID of whatever creature got the slot next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CreatureID {
+  pub index: u32,
+  pub generation: u32,
+}
+
+/// Hands out the `CreatureID` values for one world.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CreatureIdAllocator {
+  pub(crate) generations: Vec<u32>,
+  pub(crate) live: Vec<bool>,
+  pub(crate) free_list: Vec<u32>,
+}
+
+impl CreatureIdAllocator {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Gives out an ID that isn't currently in use.
+  pub fn allocate(&mut self) -> CreatureID {
+    match self.free_list.pop() {
+      Some(index) => {
+        let i = index as usize;
+        self.generations[i] = self.generations[i].wrapping_add(1);
+        self.live[i] = true;
+        CreatureID {
+          index,
+          generation: self.generations[i],
+        }
+      }
+      None => {
+        let index = self.generations.len() as u32;
+        self.generations.push(0);
+        self.live.push(true);
+        CreatureID { index, generation: 0 }
+      }
+    }
+  }
+
+  /// Marks the ID as no longer in use. Gives `false` if the ID was already
+  /// stale.
+  pub fn free(&mut self, id: CreatureID) -> bool {
+    if self.is_live(id) {
+      self.live[id.index as usize] = false;
+      self.free_list.push(id.index);
+      true
+    } else {
+      false
+    }
+  }
+
+  /// If this ID is for a creature that still exists.
+  pub fn is_live(&self, id: CreatureID) -> bool {
+    let i = id.index as usize;
+    i < self.generations.len() && self.live[i] && self.generations[i] == id.generation
+  }
+}
+
+#[test]
+fn test_creature_id_generations() {
+  let mut ids = CreatureIdAllocator::new();
+  let first = ids.allocate();
+  let second = ids.allocate();
+  assert!(ids.free(first));
+  assert!(!ids.free(first));
+  let reused = ids.allocate();
+  assert_eq!(reused.index, first.index);
+  assert!(reused != first);
+  assert!(!ids.is_live(first));
+  assert!(ids.is_live(second));
+  assert!(ids.is_live(reused));
+}
+
+#[derive(Debug, Default)]
+pub struct CreatureStore {
+  pub(crate) ids: CreatureIdAllocator,
+  /// Indexed by `CreatureID::index`, along with where that creature is.
+  pub(crate) slots: Vec<Option<(Creature, Location)>>,
+  by_location: HashMap<Location, CreatureID>,
+  player_id: Option<CreatureID>,
+}
+
+impl CreatureStore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// An empty store that will use the ID allocator given. Creatures with IDs
+  /// that are live in the allocator can then be `put` back in.
+  pub(crate) fn with_ids(ids: CreatureIdAllocator) -> Self {
+    CreatureStore {
+      ids,
+      ..Self::default()
+    }
+  }
+
+  /// Makes a new creature at the location given, if no one is already there.
+  ///
+  /// The function gets the new creature's ID and has to make the creature.
+  pub fn spawn<F>(&mut self, location: Location, make_creature: F) -> Option<CreatureID>
+  where
+    F: FnOnce(CreatureID) -> Creature,
+  {
+    if self.by_location.contains_key(&location) {
+      None
+    } else {
+      let id = self.ids.allocate();
+      let creature = make_creature(id);
+      debug_assert_eq!(creature.id, id);
+      self.put(creature, location);
+      Some(id)
+    }
+  }
+
+  /// Places a creature that already has a live ID into its slot.
+  pub(crate) fn put(&mut self, creature: Creature, location: Location) {
+    let id = creature.id;
+    debug_assert!(self.ids.is_live(id));
+    debug_assert!(self.get(id).is_none());
+    debug_assert!(!self.by_location.contains_key(&location));
+    let i = id.index as usize;
+    while self.slots.len() <= i {
+      self.slots.push(None);
+    }
+    if creature.is_the_player {
+      self.player_id = Some(id);
+    }
+    self.by_location.insert(location, id);
+    self.slots[i] = Some((creature, location));
+  }
+
+  /// Takes a creature out of the world entirely.
+  pub fn remove(&mut self, id: CreatureID) -> Option<(Creature, Location)> {
+    if self.ids.free(id) {
+      let (creature, location) = self.slots[id.index as usize].take().expect("Creature slots out of sync!");
+      self.by_location.remove(&location);
+      if self.player_id == Some(id) {
+        self.player_id = None;
+      }
+      Some((creature, location))
+    } else {
+      None
+    }
+  }
+
+  pub fn get(&self, id: CreatureID) -> Option<&Creature> {
+    self.slot(id).map(|&(ref creature, _)| creature)
+  }
+
+  pub fn get_mut(&mut self, id: CreatureID) -> Option<&mut Creature> {
+    if self.ids.is_live(id) {
+      self.slots.get_mut(id.index as usize).and_then(|slot| slot.as_mut()).map(|&mut (ref mut creature, _)| creature)
+    } else {
+      None
+    }
+  }
+
+  pub fn location_of(&self, id: CreatureID) -> Option<Location> {
+    self.slot(id).map(|&(_, location)| location)
+  }
+
+  /// The ID of whoever is standing at the location, if anyone.
+  pub fn id_at(&self, location: Location) -> Option<CreatureID> {
+    self.by_location.get(&location).cloned()
+  }
+
+  pub fn at(&self, location: Location) -> Option<&Creature> {
+    self.id_at(location).and_then(|id| self.get(id))
+  }
+
+  /// Moves a creature to an empty location. Gives `false` (and doesn't move
+  /// anything) if the creature doesn't exist or the spot is taken.
+  pub fn move_to(&mut self, id: CreatureID, destination: Location) -> bool {
+    if !self.ids.is_live(id) || self.by_location.contains_key(&destination) {
+      return false;
+    }
+    let slot = self.slots[id.index as usize].as_mut().expect("Creature slots out of sync!");
+    self.by_location.remove(&slot.1);
+    slot.1 = destination;
+    self.by_location.insert(destination, id);
+    true
+  }
+
+  pub fn player_id(&self) -> Option<CreatureID> {
+    self.player_id
+  }
+
+  pub fn player(&self) -> Option<&Creature> {
+    self.player_id.and_then(|id| self.get(id))
+  }
+
+  pub fn player_mut(&mut self) -> Option<&mut Creature> {
+    match self.player_id {
+      Some(id) => self.get_mut(id),
+      None => None,
+    }
+  }
+
+  /// How many creatures there are.
+  pub fn len(&self) -> usize {
+    self.by_location.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.by_location.is_empty()
+  }
+
+  /// Every creature along with its location, in ID index order.
+  pub fn iter(&self) -> impl Iterator<Item = (&Creature, Location)> {
+    self
+      .slots
+      .iter()
+      .filter_map(|slot| slot.as_ref().map(|&(ref creature, location)| (creature, location)))
+  }
+
+  /// Every creature, in ID index order, so their stats can be changed. Use
+  /// `move_to` to change where they are.
+  pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Creature> {
+    self.slots.iter_mut().filter_map(|slot| slot.as_mut().map(|&mut (ref mut creature, _)| creature))
+  }
+
+  /// Every creature ID, in index order. This is a copy, so the store can be
+  /// changed while going through it.
+  pub fn ids(&self) -> Vec<CreatureID> {
+    self.iter().map(|(creature, _)| creature.id).collect()
+  }
+
+  fn slot(&self, id: CreatureID) -> Option<&(Creature, Location)> {
+    if self.ids.is_live(id) {
+      self.slots.get(id.index as usize).and_then(|slot| slot.as_ref())
+    } else {
+      None
+    }
+  }
+}
+
+#[test]
+fn test_creature_store_indexes() {
+  let mut store = CreatureStore::new();
+  let here = Location { x: 1, y: 1 };
+  let there = Location { x: 2, y: 1 };
+  let player_id = store.spawn(here, Creature::new_player).unwrap();
+  assert!(store.spawn(here, Creature::new_player).is_none());
+  let monster_id = store.spawn(there, |id| Creature::new(id, b'k', 0)).unwrap();
+  assert_eq!(store.player_id(), Some(player_id));
+  assert_eq!(store.id_at(there), Some(monster_id));
+
+  assert!(!store.move_to(player_id, there));
+  assert!(store.move_to(player_id, Location { x: 1, y: 2 }));
+  assert_eq!(store.id_at(here), None);
+  assert_eq!(store.location_of(player_id), Some(Location { x: 1, y: 2 }));
+
+  let (removed, removed_location) = store.remove(monster_id).unwrap();
+  assert_eq!(removed.id, monster_id);
+  assert_eq!(removed_location, there);
+  assert_eq!(store.id_at(there), None);
+  assert!(store.get(monster_id).is_none());
+  let new_monster = store.spawn(there, |id| Creature::new(id, b'k', 0)).unwrap();
+  assert_eq!(new_monster.index, monster_id.index);
+  assert!(store.get(monster_id).is_none());
+  assert_eq!(store.len(), 2);
+}
@@ -0,0 +1,223 @@
+//! A tiny text format for game data definitions.
+//!
+//! A file is a list of sections. Each section starts with a name in square
+//! brackets, and then has one `key = value` line per field. Blank lines and
+//! lines starting with `#` are skipped.
+//!
+//! ```text
+//! # the basic monster
+//! [kestrel]
+//! glyph = k
+//! color = 166 0 0
+//! ```
+
+use super::*;
+
+/// Something with a rarity of `r` is `RARITY_SCALE / r` likely to be picked.
+pub const RARITY_SCALE: u32 = 60;
+
+/// Something was wrong with a definition file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefError {
+  /// The line (starting from 1) where the problem is.
+  pub line: usize,
+  pub message: String,
+}
+
+impl ::std::fmt::Display for DefError {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+/// One `[name]` section and all of its fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefSection {
+  pub name: String,
+  pub line: usize,
+  /// Each field's key, value, and line number, in file order.
+  pub fields: Vec<(String, String, usize)>,
+}
+
+impl DefSection {
+  fn error(&self, line: usize, message: String) -> DefError {
+    DefError { line, message }
+  }
+
+  /// The raw text of a field that has to be there.
+  pub fn get_str(&self, key: &str) -> Result<&str, DefError> {
+    self
+      .fields
+      .iter()
+      .find(|&&(ref k, _, _)| k == key)
+      .map(|&(_, ref v, _)| v.as_ref())
+      .ok_or_else(|| self.error(self.line, format!("[{}] is missing `{}`", self.name, key)))
+  }
+
+  /// Parses a field with `FromStr`.
+  pub fn get<T: ::std::str::FromStr>(&self, key: &str) -> Result<T, DefError> {
+    let text = self.get_str(key)?;
+    text
+      .parse()
+      .map_err(|_| self.error(self.line_of(key), format!("`{}` can't be {:?}", key, text)))
+  }
+
+  /// If the section has a field at all.
+  pub fn has(&self, key: &str) -> bool {
+    self.fields.iter().any(|&(ref k, _, _)| k == key)
+  }
+
+  /// Like `get`, but a missing field gives the default instead of an error.
+  pub fn get_or<T: ::std::str::FromStr>(&self, key: &str, default: T) -> Result<T, DefError> {
+    if self.has(key) {
+      self.get(key)
+    } else {
+      Ok(default)
+    }
+  }
+
+  /// A glyph is either a single printable character, or a number for one of
+  /// the fancier tiles in the font.
+  pub fn get_glyph(&self, key: &str) -> Result<u8, DefError> {
+    let text = self.get_str(key)?;
+    let bytes = text.as_bytes();
+    if bytes.len() == 1 && bytes[0].is_ascii_graphic() {
+      Ok(bytes[0])
+    } else {
+      text
+        .parse()
+        .map_err(|_| self.error(self.line_of(key), format!("`{}` isn't a glyph: {:?}", key, text)))
+    }
+  }
+
+  /// A color is written as three numbers: red, green, and blue.
+  pub fn get_color(&self, key: &str) -> Result<u32, DefError> {
+    let text = self.get_str(key)?;
+    let channels: Vec<Option<u8>> = text.split_whitespace().map(|part| part.parse().ok()).collect();
+    match channels.as_slice() {
+      [Some(r), Some(g), Some(b)] => Ok(rgb32!(*r, *g, *b)),
+      _ => Err(self.error(self.line_of(key), format!("`{}` isn't a color like `255 128 0`: {:?}", key, text))),
+    }
+  }
+
+  /// Two numbers, low then high, like a depth range.
+  pub fn get_range(&self, key: &str) -> Result<(u32, u32), DefError> {
+    let text = self.get_str(key)?;
+    let parts: Vec<Option<u32>> = text.split_whitespace().map(|part| part.parse().ok()).collect();
+    match parts.as_slice() {
+      [Some(low), Some(high)] if low <= high => Ok((*low, *high)),
+      _ => Err(self.error(self.line_of(key), format!("`{}` isn't a range like `1 5`: {:?}", key, text))),
+    }
+  }
+
+  /// A rarity has to be from 1 to `RARITY_SCALE`.
+  pub fn get_rarity(&self, key: &str) -> Result<u32, DefError> {
+    let rarity = self.get(key)?;
+    if rarity < 1 || rarity > RARITY_SCALE {
+      Err(self.error(self.line_of(key), format!("`{}` has to be from 1 to {}", key, RARITY_SCALE)))
+    } else {
+      Ok(rarity)
+    }
+  }
+
+  fn line_of(&self, key: &str) -> usize {
+    self
+      .fields
+      .iter()
+      .find(|&&(ref k, _, _)| k == key)
+      .map(|&(_, _, line)| line)
+      .unwrap_or(self.line)
+  }
+}
+
+/// Picks one of the choices, with the rare ones less likely. Gives `None` if
+/// there's nothing to pick.
+pub fn pick_by_rarity<'a, T, F>(choices: Vec<&'a T>, rarity_of: F, gen: &mut PCG32) -> Option<&'a T>
+where
+  F: Fn(&T) -> u32,
+{
+  let total: u32 = choices.iter().map(|choice| RARITY_SCALE / rarity_of(choice)).sum();
+  if total == 0 {
+    return None;
+  }
+  let mut roll = gen.next_u32() % total;
+  for choice in choices.into_iter() {
+    let weight = RARITY_SCALE / rarity_of(choice);
+    if roll < weight {
+      return Some(choice);
+    }
+    roll -= weight;
+  }
+  unreachable!("roll was past the total weight")
+}
+
+/// Splits definition text into its sections.
+pub fn parse_def_sections(text: &str) -> Result<Vec<DefSection>, DefError> {
+  let mut out: Vec<DefSection> = vec![];
+  for (i, raw_line) in text.lines().enumerate() {
+    let line = i + 1;
+    let trimmed = raw_line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+      continue;
+    } else if trimmed.starts_with('[') && trimmed.ends_with(']') {
+      let name = trimmed[1..trimmed.len() - 1].trim().to_string();
+      if name.is_empty() {
+        return Err(DefError {
+          line,
+          message: "a section needs a name".to_string(),
+        });
+      }
+      if out.iter().any(|section| section.name == name) {
+        return Err(DefError {
+          line,
+          message: format!("[{}] is defined twice", name),
+        });
+      }
+      out.push(DefSection { name, line, fields: vec![] });
+    } else {
+      let mut halves = trimmed.splitn(2, '=');
+      let key = halves.next().unwrap().trim();
+      let value = match halves.next() {
+        Some(value) if !key.is_empty() => value.trim(),
+        _ => {
+          return Err(DefError {
+            line,
+            message: format!("expected `key = value`, found {:?}", trimmed),
+          })
+        }
+      };
+      match out.last_mut() {
+        Some(section) => {
+          if section.fields.iter().any(|&(ref k, _, _)| k == key) {
+            return Err(DefError {
+              line,
+              message: format!("`{}` is given twice", key),
+            });
+          }
+          section.fields.push((key.to_string(), value.to_string(), line));
+        }
+        None => {
+          return Err(DefError {
+            line,
+            message: "fields have to be inside a [section]".to_string(),
+          })
+        }
+      }
+    }
+  }
+  Ok(out)
+}
+
+#[test]
+fn test_parse_def_sections() {
+  let text = "# a comment\n[thing]\nglyph = t\ncolor = 1 2 3\n\n[other]\nhp = 4\n";
+  let sections = parse_def_sections(text).unwrap();
+  assert_eq!(sections.len(), 2);
+  assert_eq!(sections[0].get_glyph("glyph"), Ok(b't'));
+  assert_eq!(sections[0].get_color("color"), Ok(rgb32!(1, 2, 3)));
+  assert_eq!(sections[1].get::<i32>("hp"), Ok(4));
+  assert_eq!(sections[1].get_or::<i32>("speed", 10), Ok(10));
+  assert_eq!(sections[1].get::<i32>("nope").map_err(|e| e.line), Err(6));
+  assert_eq!(parse_def_sections("hp = 4").map_err(|e| e.line), Err(1));
+  assert_eq!(parse_def_sections("[a]\n[a]").map_err(|e| e.line), Err(2));
+}
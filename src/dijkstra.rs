@@ -0,0 +1,333 @@
+//! Dijkstra maps, which say how far every tile is from the nearest of some
+//! goals.
+//!
+//! One search fills in the whole map, and then anyone can walk toward the
+//! goals by always stepping downhill. That makes a map much cheaper than an
+//! `a_star` search per monster once more than a few monsters want to go to
+//! the same place. The world keeps a few common maps that get shared by every
+//! monster during a world turn.
+
+use super::*;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::rc::Rc;
+
+/// The value of any tile that can't reach a goal at all.
+pub const UNREACHABLE: i32 = ::std::i32::MAX;
+
+/// How much a fleeing map stretches out the distances of the map it's made
+/// from, in tenths. Anything over 10 makes creatures willing to run past the
+/// threat to get to a bigger open area instead of backing into a corner.
+pub const FLEE_STRETCH_TENTHS: i32 = 12;
+
+/// A value for every tile, lower is closer to a goal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DijkstraMap {
+  width: usize,
+  height: usize,
+  movement: Movement,
+  values: Vec<i32>,
+}
+
+impl DijkstraMap {
+  /// Makes a map of the distance (in steps) to the nearest goal, only going
+  /// through walkable tiles.
+  pub fn new<W>(terrain: &TerrainMap, goals: &[Location], movement: Movement, walkable: W) -> Self
+  where
+    W: Fn(Location) -> bool,
+  {
+    Self::from_seeds(terrain, goals.iter().map(|&goal| (goal, 0)), movement, walkable)
+  }
+
+  /// Makes a map from starting values for some tiles. Every other walkable
+  /// tile ends up 1 more than its lowest neighbor.
+  pub fn from_seeds<I, W>(terrain: &TerrainMap, seeds: I, movement: Movement, walkable: W) -> Self
+  where
+    I: IntoIterator<Item = (Location, i32)>,
+    W: Fn(Location) -> bool,
+  {
+    let mut out = DijkstraMap {
+      width: terrain.width(),
+      height: terrain.height(),
+      movement,
+      values: vec![UNREACHABLE; terrain.width() * terrain.height()],
+    };
+    let mut open_set = BinaryHeap::new();
+    for (loc, value) in seeds.into_iter() {
+      if let Some(i) = out.index_of(loc) {
+        if value < out.values[i] {
+          out.values[i] = value;
+          open_set.push(Reverse((value, loc.x, loc.y)));
+        }
+      }
+    }
+    while let Some(Reverse((value, x, y))) = open_set.pop() {
+      let loc = Location { x, y };
+      if value > out.values[out.index_of(loc).unwrap()] {
+        // we already found a better way here.
+        continue;
+      }
+      for neighbor in out.neighbors(loc).filter(|&neighbor| walkable(neighbor)) {
+        if let Some(i) = out.index_of(neighbor) {
+          if value + 1 < out.values[i] {
+            out.values[i] = value + 1;
+            open_set.push(Reverse((value + 1, neighbor.x, neighbor.y)));
+          }
+        }
+      }
+    }
+    out
+  }
+
+  /// Makes a map for running away from the goals of this map.
+  pub fn fleeing<W>(&self, terrain: &TerrainMap, walkable: W) -> Self
+  where
+    W: Fn(Location) -> bool,
+  {
+    let seeds: Vec<(Location, i32)> = (0..self.values.len())
+      .filter(|&i| self.values[i] != UNREACHABLE)
+      .map(|i| (self.location_of(i), -self.values[i] * FLEE_STRETCH_TENTHS / 10))
+      .collect();
+    Self::from_seeds(terrain, seeds, self.movement, walkable)
+  }
+
+  /// The locations that are one step away from a location on this map.
+  fn neighbors(&self, loc: Location) -> impl Iterator<Item = Location> {
+    let count = match self.movement {
+      Movement::FourWay => 4,
+      Movement::EightWay => 8,
+    };
+    EIGHT_WAY_DELTAS[..count].iter().map(move |&delta| loc + delta)
+  }
+
+  fn index_of(&self, loc: Location) -> Option<usize> {
+    if loc.x >= 0 && loc.y >= 0 && (loc.x as usize) < self.width && (loc.y as usize) < self.height {
+      Some(loc.y as usize * self.width + loc.x as usize)
+    } else {
+      None
+    }
+  }
+
+  fn location_of(&self, i: usize) -> Location {
+    Location {
+      x: (i % self.width) as i32,
+      y: (i / self.width) as i32,
+    }
+  }
+
+  /// The value of a tile, or `None` if it can't reach a goal.
+  pub fn get(&self, loc: Location) -> Option<i32> {
+    self.index_of(loc).map(|i| self.values[i]).filter(|&value| value != UNREACHABLE)
+  }
+
+  /// The step to the lowest of the neighbors that `open` allows, as a delta.
+  /// Gives `None` if none of them are lower than where it already is.
+  pub fn downhill<O>(&self, from: Location, open: O) -> Option<Location>
+  where
+    O: Fn(Location) -> bool,
+  {
+    let mut best = None;
+    let mut best_value = self.get(from).unwrap_or(UNREACHABLE);
+    for neighbor in self.neighbors(from).filter(|&neighbor| open(neighbor)) {
+      if let Some(value) = self.get(neighbor) {
+        if value < best_value {
+          best = Some(neighbor - from);
+          best_value = value;
+        }
+      }
+    }
+    best
+  }
+}
+
+/// The maps that get shared by every monster for one world turn. They
+/// depend on the terrain, where the player is, and where the items are, so
+/// anything that changes one of those has to clear them.
+#[derive(Debug, Default)]
+pub struct FlowFields {
+  toward_player: Option<Rc<DijkstraMap>>,
+  away_from_player: Option<Rc<DijkstraMap>>,
+  toward_items: Option<Rc<DijkstraMap>>,
+}
+
+impl GameWorld {
+  /// Throws out the shared maps, so that they get made again the next time
+  /// that they're needed.
+  pub(crate) fn clear_flow_fields(&self) {
+    *self.flow_fields.borrow_mut() = FlowFields::default();
+  }
+
+  /// How far every tile is from the player.
+  pub fn toward_player_map(&self) -> Rc<DijkstraMap> {
+    if let Some(ref map) = self.flow_fields.borrow().toward_player {
+      return map.clone();
+    }
+    let map = Rc::new(DijkstraMap::new(&self.terrain, &[self.player_location], Movement::EightWay, |loc| {
+      self.terrain.get(loc).is_walkable()
+    }));
+    self.flow_fields.borrow_mut().toward_player = Some(map.clone());
+    map
+  }
+
+  /// Stepping downhill on this runs away from the player.
+  pub fn away_from_player_map(&self) -> Rc<DijkstraMap> {
+    if let Some(ref map) = self.flow_fields.borrow().away_from_player {
+      return map.clone();
+    }
+    let map = Rc::new(self.toward_player_map().fleeing(&self.terrain, |loc| self.terrain.get(loc).is_walkable()));
+    self.flow_fields.borrow_mut().away_from_player = Some(map.clone());
+    map
+  }
+
+  /// How far every tile is from the nearest item on the floor.
+  pub fn toward_items_map(&self) -> Rc<DijkstraMap> {
+    if let Some(ref map) = self.flow_fields.borrow().toward_items {
+      return map.clone();
+    }
+    let mut goals: Vec<Location> = self
+      .item_locations
+      .iter()
+      .filter(|&(_, items)| !items.is_empty())
+      .map(|(&loc, _)| loc)
+      .collect();
+    goals.sort_by_key(|loc| (loc.y, loc.x));
+    let map = Rc::new(DijkstraMap::new(&self.terrain, &goals, Movement::EightWay, |loc| {
+      self.terrain.get(loc).is_walkable()
+    }));
+    self.flow_fields.borrow_mut().toward_items = Some(map.clone());
+    map
+  }
+}
+
+#[test]
+fn test_dijkstra_map_matches_a_star() {
+  let world = GameWorld::new(4321);
+  let walkable = |loc: Location| world.terrain.get(loc).is_walkable();
+  let map = DijkstraMap::new(&world.terrain, &[world.player_location], Movement::FourWay, walkable);
+  let eight_way_map = world.toward_player_map();
+  let eight_way = PathOptions {
+    movement: Movement::EightWay,
+    ..PathOptions::default()
+  };
+  let mut gen = PCG32::new(1);
+  for _ in 0..20 {
+    let spot = loop {
+      let spot = Location {
+        x: (gen.next_u32() % world.terrain.width() as u32) as i32,
+        y: (gen.next_u32() % world.terrain.height() as u32) as i32,
+      };
+      if walkable(spot) {
+        break spot;
+      }
+    };
+    let path = a_star(world.player_location, spot, walkable);
+    assert_eq!(map.get(spot), path.map(|path| path.len() as i32 - 1), "to {:?}", spot);
+    // with diagonals a step is a step, so the map can only beat the path
+    // that's shortest by distance.
+    let diagonal_path = find_path(world.player_location, spot, eight_way, |loc| if walkable(loc) { Some(1) } else { None });
+    assert_eq!(eight_way_map.get(spot).is_some(), diagonal_path.is_some());
+    if let (Some(steps), Some(path)) = (eight_way_map.get(spot), diagonal_path) {
+      assert!(steps <= path.len() as i32 - 1 && steps <= map.get(spot).unwrap());
+    }
+  }
+  // the same map gets handed out until it's cleared
+  assert!(Rc::ptr_eq(&eight_way_map, &world.toward_player_map()));
+  world.clear_flow_fields();
+  assert!(!Rc::ptr_eq(&eight_way_map, &world.toward_player_map()));
+}
+
+#[test]
+fn test_fleeing_and_item_maps() {
+  let mut terrain = TerrainMap::new(12, 1, Terrain::Floor);
+  terrain.set(Location { x: 11, y: 0 }, Terrain::Wall);
+  let walkable = |loc: Location| terrain.get(loc).is_walkable();
+  let toward = DijkstraMap::new(&terrain, &[Location { x: 3, y: 0 }], Movement::FourWay, walkable);
+  assert_eq!(toward.get(Location { x: 0, y: 0 }), Some(3));
+  assert_eq!(toward.get(Location { x: 10, y: 0 }), Some(7));
+  assert_eq!(toward.get(Location { x: 11, y: 0 }), None);
+  assert_eq!(toward.downhill(Location { x: 5, y: 0 }, |_| true), Some(Location { x: -1, y: 0 }));
+
+  // on either side of the threat, fleeing goes further away from it
+  let away = toward.fleeing(&terrain, walkable);
+  assert_eq!(away.downhill(Location { x: 2, y: 0 }, |_| true), Some(Location { x: -1, y: 0 }));
+  assert_eq!(away.downhill(Location { x: 4, y: 0 }, |_| true), Some(Location { x: 1, y: 0 }));
+  assert_eq!(away.downhill(Location { x: 4, y: 0 }, |loc| loc.x != 5), None);
+
+  let mut world = GameWorld::new(55);
+  world.item_locations.clear();
+  let item_spot = world.player_location + Location { x: 0, y: 1 };
+  world.terrain.set(item_spot, Terrain::Floor);
+  world.item_locations.insert(item_spot, vec![Item(0)]);
+  assert_eq!(world.toward_items_map().get(item_spot), Some(0));
+  assert_eq!(world.toward_items_map().get(world.player_location), Some(1));
+}
+
+#[test]
+fn test_item_map_matches_a_star() {
+  // a small map keeps all the a_star runs quick
+  let mut world = GameWorld::new(8765);
+  let mut gen = PCG32::new(2);
+  world.terrain = TerrainMap::new(16, 10, Terrain::Floor);
+  for _ in 0..40 {
+    let spot = Location {
+      x: (gen.next_u32() % 16) as i32,
+      y: (gen.next_u32() % 10) as i32,
+    };
+    world.terrain.set(spot, Terrain::Wall);
+  }
+  let item_spots = vec![Location { x: 0, y: 0 }, Location { x: 15, y: 9 }, Location { x: 8, y: 5 }];
+  world.item_locations.clear();
+  for &spot in item_spots.iter() {
+    world.terrain.set(spot, Terrain::Floor);
+    world.item_locations.insert(spot, vec![Item(0)]);
+  }
+  world.clear_flow_fields();
+  let walkable = |loc: Location| world.terrain.get(loc).is_walkable();
+  let map = world.toward_items_map();
+  let four_way = DijkstraMap::new(&world.terrain, &item_spots, Movement::FourWay, walkable);
+  for y in 0..10 {
+    for x in 0..16 {
+      let spot = Location { x, y };
+      if !walkable(spot) {
+        continue;
+      }
+      // the closest item by a_star is how far the four way map says it is
+      let nearest = item_spots
+        .iter()
+        .filter_map(|&item_spot| a_star(spot, item_spot, walkable))
+        .map(|path| path.len() as i32 - 1)
+        .min();
+      assert_eq!(four_way.get(spot), nearest, "from {:?}", spot);
+      // and diagonals can only make it closer
+      assert_eq!(map.get(spot).is_some(), nearest.is_some());
+      if let (Some(steps), Some(nearest)) = (map.get(spot), nearest) {
+        assert!(steps <= nearest);
+      }
+    }
+  }
+}
+
+#[test]
+fn test_changing_the_map_clears_the_flow_fields() {
+  let mut world = GameWorld::test_arena(66);
+  let player_id = world.creatures.player_id().unwrap();
+  let spot = world.player_location + Location { x: 2, y: 0 };
+  let before = world.toward_player_map();
+  assert_eq!(before.get(spot), Some(2));
+  let ice_bomb = world.item_registry.by_name("Ice Bomb").unwrap();
+  world.apply_item_effects(ice_bomb, spot, player_id);
+  assert_eq!(world.toward_player_map().get(spot), None);
+
+  // and so does a monster dying and dropping what it had
+  let drop_spot = world.player_location + Location { x: -2, y: 0 };
+  world.item_locations.clear();
+  world.clear_flow_fields();
+  assert_eq!(world.toward_items_map().get(drop_spot), None);
+  let monster_id = world.spawn_named_at("kestrel", drop_spot);
+  let monster_mut = world.creatures.get_mut(monster_id).unwrap();
+  monster_mut.inventory = vec![ice_bomb];
+  monster_mut.hit_points = 0;
+  world.remove_the_dead();
+  assert_eq!(world.toward_items_map().get(drop_spot), Some(0));
+}
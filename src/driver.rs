@@ -0,0 +1,214 @@
+//! The part of the game loop that doesn't care what the frontend is.
+//!
+//! A frontend turns its own input into `Command` values, passes them to a
+//! `GameDriver`, and then asks the driver to draw into a `Screen`. Which
+//! screen gets drawn and what a command means both depend on the current
+//! `DisplayMode`, which the driver keeps track of.
+
+use super::*;
+
+/// How far the player has to see to fill the map part of a screen this big.
+/// The player is drawn in the middle, so that's half of the longer side.
+pub fn fov_range_for(width: usize, height: usize) -> i32 {
+  (width / 2).max(height.saturating_sub(STATUS_HEIGHT) / 2) as i32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayMode {
+  Game,
+  Inventory,
+  ItemTargeting(char, Location),
+  /// Looking around the map, with the cursor this far from the player.
+  Look(Location),
+  /// The player is dead, and nothing else can happen.
+  GameOver,
+}
+
+/// A single input from the player, after the frontend has decoded it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+  /// Walk (or move the cursor) by this much, diagonals included.
+  Move(Location),
+  /// Stay put for a turn.
+  Rest,
+  OpenInventory,
+  /// Start looking around the map.
+  Look,
+  /// Take the stairs that the player is on, up or down.
+  UseStairs,
+  /// Scroll the message panel back to older messages.
+  OlderMessages,
+  /// Scroll the message panel forward to newer messages.
+  NewerMessages,
+  SelectItem(char),
+  /// Take off whatever is in an equipment slot.
+  Unequip(EquipSlot),
+  Confirm,
+  Cancel,
+  /// Close the game. The frontend takes care of this one, so the driver
+  /// ignores it.
+  Quit,
+}
+
+#[derive(Debug)]
+pub struct GameDriver {
+  pub game: GameWorld,
+  pub display_mode: DisplayMode,
+  /// If this is set, every player action gets recorded into it.
+  pub action_log: Option<ActionLog>,
+  /// How far back the message panel is scrolled.
+  pub message_scroll: usize,
+}
+
+impl GameDriver {
+  /// Takes over a game, for a frontend with a screen of the size given. The
+  /// player gets to see as far as the screen shows, so any log has to be
+  /// brand new for it to play back the same.
+  pub fn new(mut game: GameWorld, mut action_log: Option<ActionLog>, width: usize, height: usize) -> Self {
+    let fov_range = fov_range_for(width, height);
+    game.set_fov_range(fov_range);
+    if let Some(log) = action_log.as_mut() {
+      log.fov_range = fov_range;
+    }
+    let mut out = GameDriver {
+      game,
+      display_mode: DisplayMode::Game,
+      action_log,
+      message_scroll: 0,
+    };
+    if out.game.game_over().is_some() {
+      out.display_mode = DisplayMode::GameOver;
+    }
+    out
+  }
+
+  /// Does whatever the command means in the current display mode.
+  pub fn handle(&mut self, command: Command) {
+    match self.display_mode {
+      DisplayMode::Game => match command {
+        Command::Move(delta) => self.perform(PlayerAction::Move(delta)),
+        Command::Rest => self.perform(PlayerAction::Rest),
+        Command::OpenInventory => self.display_mode = DisplayMode::Inventory,
+        Command::Look => self.display_mode = DisplayMode::Look(Location { x: 0, y: 0 }),
+        Command::OlderMessages => {
+          if self.message_scroll + 1 < self.game.messages.len() {
+            self.message_scroll += 1;
+          }
+        }
+        Command::NewerMessages => self.message_scroll = self.message_scroll.saturating_sub(1),
+        Command::UseStairs => {
+          if self.game.use_stairs() {
+            self.finish_action(PlayerAction::UseStairs);
+          }
+        }
+        _ => {}
+      },
+      DisplayMode::Inventory => match command {
+        Command::Cancel => self.display_mode = DisplayMode::Game,
+        Command::SelectItem(letter) => match self.game.use_item(letter) {
+          UseItemResult::NoSuchItem => {}
+          UseItemResult::ItemUsed => {
+            self.display_mode = DisplayMode::Game;
+            self.finish_action(PlayerAction::UseItem(letter));
+          }
+          UseItemResult::ItemNeedsTarget => {
+            self.display_mode = DisplayMode::ItemTargeting(letter, Location { x: 0, y: 0 });
+          }
+        },
+        Command::Unequip(slot) => {
+          if self.game.unequip(slot) {
+            self.display_mode = DisplayMode::Game;
+            self.finish_action(PlayerAction::Unequip(slot));
+          }
+        }
+        _ => {}
+      },
+      DisplayMode::ItemTargeting(letter, delta) => match command {
+        Command::Cancel => self.display_mode = DisplayMode::Game,
+        Command::Confirm => {
+          self.display_mode = DisplayMode::Game;
+          if self.game.use_targeted_item(letter, delta) {
+            self.finish_action(PlayerAction::UseTargetedItem(letter, delta));
+          }
+        }
+        Command::Move(delta_change) => {
+          let new_delta = delta + delta_change;
+          if self.game.visible.contains(&(self.game.player_location + new_delta)) {
+            self.display_mode = DisplayMode::ItemTargeting(letter, new_delta);
+          }
+        }
+        _ => {}
+      },
+      DisplayMode::Look(delta) => match command {
+        Command::Cancel => self.display_mode = DisplayMode::Game,
+        Command::Move(delta_change) => {
+          let new_delta = delta + delta_change;
+          if self.game.can_look_at(self.game.player_location + new_delta) {
+            self.display_mode = DisplayMode::Look(new_delta);
+          }
+        }
+        _ => {}
+      },
+      DisplayMode::GameOver => {}
+    }
+  }
+
+  /// Draws whatever the current display mode calls for.
+  pub fn draw(&self, screen: &mut Screen) {
+    match self.display_mode {
+      DisplayMode::Game => draw_game(screen, &self.game, self.message_scroll),
+      DisplayMode::Inventory => draw_inventory(screen, &self.game),
+      DisplayMode::ItemTargeting(_letter, delta) => draw_targeting(screen, &self.game, delta),
+      DisplayMode::Look(delta) => draw_look(screen, &self.game, delta),
+      DisplayMode::GameOver => draw_game_over(screen, &self.game),
+    }
+  }
+
+  /// Does a player action, recording it if we're keeping a log.
+  fn perform(&mut self, action: PlayerAction) {
+    action.apply_to(&mut self.game);
+    self.finish_action(action);
+  }
+
+  /// Catches everything up after an action has been applied to the world.
+  fn finish_action(&mut self, action: PlayerAction) {
+    let game_ref = &self.game;
+    if let Some(log) = self.action_log.as_mut() {
+      log.record(action, game_ref);
+    }
+    self.message_scroll = 0;
+    if self.game.game_over().is_some() {
+      self.display_mode = DisplayMode::GameOver;
+    }
+  }
+}
+
+#[test]
+fn test_driver_draws_headless() {
+  let mut driver = GameDriver::new(GameWorld::new(12345), None, 40, 20);
+  assert_eq!(driver.game.fov_range, 20);
+  let mut screen = Screen::new(40, 20);
+  driver.draw(&mut screen);
+  assert!(screen.to_text(false).contains('@'));
+
+  driver.handle(Command::OpenInventory);
+  assert_eq!(driver.display_mode, DisplayMode::Inventory);
+  driver.draw(&mut screen);
+  assert!(screen.to_text(false).contains("== Inventory =="));
+  driver.handle(Command::Cancel);
+  assert_eq!(driver.display_mode, DisplayMode::Game);
+
+  driver.handle(Command::Look);
+  driver.draw(&mut screen);
+  assert!(screen.to_text(false).contains("== Look =="));
+  // the cursor can't go off into the dark
+  for _ in 0..100 {
+    driver.handle(Command::Move(Location { x: 1, y: 0 }));
+  }
+  match driver.display_mode {
+    DisplayMode::Look(delta) => assert!(driver.game.can_look_at(driver.game.player_location + delta)),
+    other => panic!("expected to be looking, got {:?}", other),
+  }
+  driver.handle(Command::Cancel);
+  assert_eq!(driver.display_mode, DisplayMode::Game);
+}
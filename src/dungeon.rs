@@ -0,0 +1,245 @@
+//! The stack of levels that makes up the dungeon, and travel between them.
+//!
+//! The `GameWorld` keeps the level that the player is on in its own fields
+//! (`terrain`, `creatures`, and `item_locations`), so most code never has to
+//! think about levels at all. Every other level that's been visited is packed
+//! up into a `Level` and kept in `other_levels` until the player comes back.
+
+use super::*;
+
+/// The bottom of the dungeon. There's no way down from here.
+pub const DEEPEST_LEVEL: u32 = 10;
+
+/// Everything about a level that the player isn't on right now.
+#[derive(Debug, Default)]
+pub struct Level {
+  pub terrain: TerrainMap,
+  pub creatures: CreatureStore,
+  pub item_locations: HashMap<Location, Vec<Item>>,
+  pub map_memory: MapMemory,
+}
+
+/// The knobs for building a new level, which get harder as you go deeper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelParams {
+  /// How many monsters we _try_ to place.
+  pub monster_count: usize,
+  pub item_count: usize,
+  /// Percent chance that each cell starts as a wall before the caves are
+  /// smoothed out. Higher is more cramped.
+  pub cave_density: u32,
+}
+
+impl LevelParams {
+  pub fn for_depth(depth: u32) -> Self {
+    let below_top = depth.saturating_sub(1);
+    LevelParams {
+      monster_count: 40 + 10 * depth as usize,
+      item_count: 100usize.saturating_sub(10 * below_top as usize).max(40),
+      // deep down the caverns open up. Don't go above about 46 or the cave
+      // generator might never find a big enough cave.
+      cave_density: 45 - below_top.min(5),
+    }
+  }
+}
+
+impl GameWorld {
+  /// Replaces the current level with a brand new one for the depth given.
+  pub(crate) fn generate_level(&mut self, depth: u32) {
+    let params = LevelParams::for_depth(depth);
+    let caves = make_cellular_caves(100, 100, params.cave_density, &mut self.gen);
+    self.terrain = TerrainMap::from_caves(&caves);
+    self.creatures = CreatureStore::new();
+    self.item_locations = HashMap::new();
+
+    // add the stairs
+    if depth > 1 {
+      let up_spot = self.pick_random_floor();
+      self.terrain.set(up_spot, Terrain::StairsUp);
+    }
+    if depth < DEEPEST_LEVEL {
+      let down_spot = self.pick_random_floor();
+      self.terrain.set(down_spot, Terrain::StairsDown);
+    }
+
+    // add the enemies. If we happen to pick an occupied location the spawn
+    // fails, and we just don't add a creature for this pass of the loop.
+    for _ in 0..params.monster_count {
+      let monster_start = self.pick_random_floor();
+      if let Some(def) = self.monster_registry.pick_for_depth(depth, &mut self.gen).cloned() {
+        self.spawn_monster(&def, monster_start);
+      }
+    }
+
+    // add some items
+    for _ in 0..params.item_count {
+      let item_spot = self.pick_random_floor();
+      if let Some(new_item) = self.item_registry.pick_for_depth(depth, &mut self.gen) {
+        self.item_locations.entry(item_spot).or_insert(Vec::new()).push(new_item);
+      }
+    }
+  }
+
+  /// Puts a new monster of the kind given at a spot, with everything it
+  /// starts out carrying and wearing. Gives `None` if someone is already
+  /// there.
+  pub(crate) fn spawn_monster(&mut self, def: &MonsterDef, location: Location) -> Option<CreatureID> {
+    let gear: Vec<Item> = def.equipment.iter().filter_map(|name| self.item_registry.by_name(name)).collect();
+    let carried: Vec<Item> = def.carries.iter().filter_map(|name| self.item_registry.by_name(name)).collect();
+    let cid = self.creatures.spawn(location, |id| {
+      let mut out = Creature::from_def(id, def);
+      out.inventory = carried.iter().chain(gear.iter()).cloned().collect();
+      out
+    })?;
+    for item in gear.into_iter() {
+      self.equip_item(cid, item);
+    }
+    Some(cid)
+  }
+
+  /// Takes the stairs that the player is standing on, if any.
+  ///
+  /// Gives `false` (and doesn't use up a turn) if the player isn't on stairs,
+  /// or if there's no room for them at the other end.
+  pub fn use_stairs(&mut self) -> bool {
+    if self.game_over.is_some() {
+      return false;
+    }
+    let (new_depth, arrive_on) = match self.terrain.get(self.player_location) {
+      Terrain::StairsDown => (self.depth + 1, Terrain::StairsUp),
+      Terrain::StairsUp => (self.depth - 1, Terrain::StairsDown),
+      Terrain::Wall | Terrain::Floor | Terrain::Ice => return false,
+    };
+    let player_id = self.creatures.player_id().expect("There's no player!");
+
+    // pack up where we were, and unpack (or make) where we're going
+    let mut old_level = self.pack_level();
+    match self.other_levels.remove(&new_depth) {
+      Some(level) => self.unpack_level(level),
+      None => self.generate_level(new_depth),
+    }
+
+    let stairs_location = self
+      .terrain
+      .iter()
+      .find(|&(_, terrain)| terrain == arrive_on)
+      .map(|(loc, _)| loc)
+      .expect("This level has no stairs back!");
+    let arrival = match self.nearest_open_spot(stairs_location) {
+      Some(arrival) => arrival,
+      None => {
+        // put everything back the way it was, the new level included.
+        let new_level = self.pack_level();
+        self.other_levels.insert(new_depth, new_level);
+        self.unpack_level(old_level);
+        self.messages.push(GameEvent::StairsBlocked);
+        return false;
+      }
+    };
+    let (mut player, _) = old_level.creatures.remove(player_id).unwrap();
+    self.other_levels.insert(self.depth, old_level);
+    self.depth = new_depth;
+    self
+      .creatures
+      .spawn(arrival, move |id| {
+        player.id = id;
+        player
+      })
+      .expect("The arrival spot was taken!");
+    self.player_location = arrival;
+    self.mark_fov_dirty();
+    self.run_world_turn(STAIRS_COST);
+    true
+  }
+
+  /// Takes the current level out of the world, leaving it empty.
+  fn pack_level(&mut self) -> Level {
+    Level {
+      terrain: ::std::mem::take(&mut self.terrain),
+      creatures: ::std::mem::take(&mut self.creatures),
+      item_locations: ::std::mem::take(&mut self.item_locations),
+      map_memory: ::std::mem::take(&mut self.map_memory),
+    }
+  }
+
+  /// Makes a packed up level the current one.
+  fn unpack_level(&mut self, level: Level) {
+    self.terrain = level.terrain;
+    self.creatures = level.creatures;
+    self.item_locations = level.item_locations;
+    self.map_memory = level.map_memory;
+  }
+
+  /// The closest walkable location to `start` with no one standing there, if
+  /// there's one anywhere that can be walked to.
+  fn nearest_open_spot(&self, start: Location) -> Option<Location> {
+    let mut open_list = ::std::collections::VecDeque::new();
+    let mut closed_set = HashSet::new();
+    open_list.push_back(start);
+    closed_set.insert(start);
+    while let Some(loc) = open_list.pop_front() {
+      if self.creatures.id_at(loc).is_none() {
+        return Some(loc);
+      }
+      for neighbor in loc.neighbors() {
+        if self.terrain.get(neighbor).is_walkable() && !closed_set.contains(&neighbor) {
+          closed_set.insert(neighbor);
+          open_list.push_back(neighbor);
+        }
+      }
+    }
+    None
+  }
+}
+
+#[test]
+fn test_stairs_keep_levels() {
+  let mut world = GameWorld::new(31337);
+  assert_eq!(world.depth, 1);
+  assert!(!world.use_stairs());
+  assert!(world.terrain.iter().all(|(_, t)| t != Terrain::StairsUp));
+  let down_stairs = world.terrain.iter().find(|&(_, t)| t == Terrain::StairsDown).unwrap().0;
+
+  // put the player right on the stairs and go down
+  if let Some(blocker) = world.creatures.id_at(down_stairs) {
+    world.creatures.remove(blocker);
+  }
+  let player_id = world.creatures.player_id().unwrap();
+  assert!(world.creatures.move_to(player_id, down_stairs));
+  world.player_location = down_stairs;
+  assert!(world.use_stairs());
+  assert_eq!(world.depth, 2);
+  assert_eq!(world.terrain.get(world.player_location), Terrain::StairsUp);
+  assert!(world.other_levels.contains_key(&1));
+
+  // and come right back up to the level we left
+  let first_level_terrain = world.other_levels[&1].terrain.clone();
+  assert!(world.use_stairs());
+  assert_eq!(world.depth, 1);
+  assert_eq!(world.player_location, down_stairs);
+  assert!(world.terrain == first_level_terrain);
+  assert!(world.other_levels.contains_key(&2));
+}
+
+#[test]
+fn test_stairs_need_room_at_the_other_end() {
+  let mut world = GameWorld::new(4444);
+  let start = world.player_location;
+  world.terrain.set(start, Terrain::StairsDown);
+  // the level below is nothing but the stairs, and someone is on them
+  let mut below = Level {
+    terrain: TerrainMap::new(3, 3, Terrain::Wall),
+    ..Level::default()
+  };
+  let stairs = Location { x: 1, y: 1 };
+  below.terrain.set(stairs, Terrain::StairsUp);
+  below.creatures.spawn(stairs, |id| Creature::new(id, b'k', 0)).unwrap();
+  world.other_levels.insert(2, below);
+  let turn = world.turn_count();
+
+  assert!(!world.use_stairs());
+  assert_eq!((world.depth, world.player_location, world.turn_count()), (1, start, turn));
+  assert_eq!(world.creatures.location_of(world.creatures.player_id().unwrap()), Some(start));
+  assert_eq!(world.messages.recent(0, 1).next(), Some(&GameEvent::StairsBlocked));
+  assert!(world.other_levels[&2].creatures.id_at(stairs).is_some());
+}
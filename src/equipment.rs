@@ -0,0 +1,224 @@
+//! Things that creatures wear or hold, and how they change their stats.
+//!
+//! An equipped item's bonuses get added right onto the creature's stats when
+//! it's put on, and taken back off when it's removed, so everything else can
+//! just read the stats like normal.
+
+use super::*;
+
+/// Where an item gets worn or held. A creature has room for one item in
+/// each slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EquipSlot {
+  Weapon,
+  Armor,
+  Shield,
+  Ring,
+}
+
+impl EquipSlot {
+  /// Every slot, in the order that they're listed.
+  pub const ALL: [EquipSlot; 4] = [EquipSlot::Weapon, EquipSlot::Armor, EquipSlot::Shield, EquipSlot::Ring];
+}
+
+impl ::std::str::FromStr for EquipSlot {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    match s {
+      "weapon" => Ok(EquipSlot::Weapon),
+      "armor" => Ok(EquipSlot::Armor),
+      "shield" => Ok(EquipSlot::Shield),
+      "ring" => Ok(EquipSlot::Ring),
+      _ => Err(()),
+    }
+  }
+}
+
+impl ::std::fmt::Display for EquipSlot {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    match self {
+      EquipSlot::Weapon => write!(f, "weapon"),
+      EquipSlot::Armor => write!(f, "armor"),
+      EquipSlot::Shield => write!(f, "shield"),
+      EquipSlot::Ring => write!(f, "ring"),
+    }
+  }
+}
+
+impl Creature {
+  /// Raises (or with a negative amount, lowers) one stat. Healing can take
+  /// hit points past the max, so they're only ever cut back down when the
+  /// max itself goes down.
+  pub fn raise_stat(&mut self, stat: Stat, amount: i32) {
+    match stat {
+      Stat::MaxHitPoints => {
+        self.max_hit_points += amount;
+        if amount < 0 {
+          self.hit_points = self.hit_points.min(self.max_hit_points);
+        }
+      }
+      Stat::AttackStep => self.attack_step += amount,
+      Stat::DamageStep => self.damage_step += amount,
+      Stat::Defense => self.defense += amount,
+      Stat::Armor => self.armor += amount,
+      Stat::Sight => self.sight += amount,
+      Stat::Speed => self.speed += amount,
+    }
+  }
+}
+
+impl GameWorld {
+  /// Puts on an item from a creature's inventory. Whatever was already in
+  /// that slot goes back into the inventory. Gives `false` if the item can't
+  /// be equipped or the creature isn't carrying it.
+  pub(crate) fn equip_item(&mut self, creature_id: CreatureID, item: Item) -> bool {
+    let def = self.item_registry.def(item);
+    let slot = match def.slot {
+      Some(slot) => slot,
+      None => return false,
+    };
+    let creature_mut = match self.creatures.get_mut(creature_id) {
+      Some(creature_mut) => creature_mut,
+      None => return false,
+    };
+    match creature_mut.inventory.iter().position(|&carried| carried == item) {
+      Some(i) => drop(creature_mut.inventory.remove(i)),
+      None => return false,
+    }
+    if let Some(old_item) = creature_mut.equipment.insert(slot, item) {
+      for &(stat, amount) in self.item_registry.def(old_item).bonuses.iter() {
+        creature_mut.raise_stat(stat, -amount);
+      }
+      creature_mut.inventory.push(old_item);
+    }
+    for &(stat, amount) in def.bonuses.iter() {
+      creature_mut.raise_stat(stat, amount);
+    }
+    true
+  }
+
+  /// Takes off whatever a creature has in a slot and puts it back into its
+  /// inventory.
+  pub(crate) fn unequip_slot(&mut self, creature_id: CreatureID, slot: EquipSlot) -> Option<Item> {
+    let creature_mut = self.creatures.get_mut(creature_id)?;
+    let item = creature_mut.equipment.remove(&slot)?;
+    for &(stat, amount) in self.item_registry.def(item).bonuses.iter() {
+      creature_mut.raise_stat(stat, -amount);
+    }
+    creature_mut.inventory.push(item);
+    Some(item)
+  }
+
+  /// Has the player put on an item that they're carrying, using up a turn.
+  pub(crate) fn player_equip(&mut self, item: Item) {
+    let player_id = self.creatures.player_id().expect("There's no player!");
+    if self.equip_item(player_id, item) {
+      self.messages.push(GameEvent::Equipped {
+        who: Who::of(self.creatures.player().unwrap()),
+        item_name: self.item_registry.def(item).name.clone(),
+      });
+      self.run_world_turn(EQUIP_COST);
+    }
+  }
+
+  /// Has the player take off whatever is in a slot, using up a turn.
+  ///
+  /// Gives `false` (and doesn't use up a turn) if the slot is already empty.
+  pub fn unequip(&mut self, slot: EquipSlot) -> bool {
+    if self.game_over.is_some() {
+      return false;
+    }
+    let player_id = self.creatures.player_id().expect("There's no player!");
+    match self.unequip_slot(player_id, slot) {
+      Some(item) => {
+        self.messages.push(GameEvent::Unequipped {
+          who: Who::of(self.creatures.player().unwrap()),
+          item_name: self.item_registry.def(item).name.clone(),
+        });
+        self.run_world_turn(EQUIP_COST);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Leaves everything that a creature had (carried or equipped) on the floor
+  /// where it was.
+  pub(crate) fn drop_everything(&mut self, creature: Creature, loc: Location) {
+    let dropped: Vec<Item> = creature
+      .inventory
+      .into_iter()
+      .chain(creature.equipment.into_iter().map(|(_slot, item)| item))
+      .collect();
+    if !dropped.is_empty() {
+      self.item_locations.entry(loc).or_insert(Vec::new()).extend(dropped);
+      self.clear_flow_fields();
+    }
+  }
+}
+
+#[test]
+fn test_equipment_changes_stats() {
+  let mut world = GameWorld::new(9090);
+  let dagger = world.item_registry.by_name("Bronze Dagger").unwrap();
+  let spear = world.item_registry.by_name("Bronze Spear").unwrap();
+  let player_id = world.creatures.player_id().unwrap();
+  let base_step = world.creatures.player().unwrap().damage_step;
+  world.creatures.player_mut().unwrap().inventory = vec![dagger, spear];
+
+  assert!(world.equip_item(player_id, dagger));
+  assert_eq!(world.creatures.player().unwrap().damage_step, base_step + 1);
+  // the spear takes the dagger's place
+  assert!(world.equip_item(player_id, spear));
+  let player = world.creatures.player().unwrap();
+  assert_eq!(player.damage_step, base_step + 2);
+  assert_eq!(player.inventory, vec![dagger]);
+  assert_eq!(player.equipment.get(&EquipSlot::Weapon), Some(&spear));
+
+  assert_eq!(world.unequip_slot(player_id, EquipSlot::Weapon), Some(spear));
+  assert_eq!(world.creatures.player().unwrap().damage_step, base_step);
+  assert_eq!(world.unequip_slot(player_id, EquipSlot::Weapon), None);
+  // only things that are carried can be put on
+  assert!(!world.equip_item(player_id, world.item_registry.by_name("Blast Bomb").unwrap()));
+
+  // using something wearable from the inventory puts it on, and takes a turn
+  let turn = world.turn_count();
+  assert_eq!(world.use_item('a'), UseItemResult::ItemUsed);
+  assert_eq!(world.creatures.player().unwrap().equipment.get(&EquipSlot::Weapon), Some(&dagger));
+  assert_eq!(world.turn_count(), turn + 1);
+}
+
+#[test]
+fn test_monsters_drop_their_gear() {
+  let mut world = GameWorld::test_arena(77);
+  let spear = world.item_registry.by_name("Bronze Spear").unwrap();
+  let spot = world.player_location + Location { x: 1, y: 0 };
+  let monster_id = world.spawn_named_at("kestrel", spot);
+  world.creatures.get_mut(monster_id).unwrap().inventory = vec![spear];
+  assert!(world.equip_item(monster_id, spear));
+  world.creatures.get_mut(monster_id).unwrap().hit_points = 0;
+  world.remove_the_dead();
+  assert_eq!(world.item_locations.get(&spot), Some(&vec![spear]));
+}
+
+#[test]
+fn test_gear_keeps_overhealing() {
+  let mut world = GameWorld::new(4040);
+  let ring = world.item_registry.by_name("Ring of Far Sight").unwrap();
+  let player_id = world.creatures.player_id().unwrap();
+  {
+    let player_mut = world.creatures.player_mut().unwrap();
+    player_mut.inventory = vec![ring];
+    player_mut.hit_points = player_mut.max_hit_points + 10;
+  }
+  let overhealed = world.creatures.player().unwrap().hit_points;
+  assert!(world.equip_item(player_id, ring));
+  assert_eq!(world.creatures.player().unwrap().hit_points, overhealed);
+  assert_eq!(world.unequip_slot(player_id, EquipSlot::Ring), Some(ring));
+  assert_eq!(world.creatures.player().unwrap().hit_points, overhealed);
+
+  // losing max hit points does cut them down, though
+  let player_mut = world.creatures.player_mut().unwrap();
+  player_mut.raise_stat(Stat::MaxHitPoints, -5);
+  assert_eq!(player_mut.hit_points, player_mut.max_hit_points);
+}
@@ -0,0 +1,378 @@
+//! The kinds of item that can show up, loaded from `data/items.txt`, and what
+//! they do when used.
+
+use super::*;
+
+/// The item definitions that are built into the game.
+pub const ITEM_DEFS_TEXT: &str = include_str!("../data/items.txt");
+
+/// How many random spots a teleport tries before giving up.
+pub const TELEPORT_TRIES: usize = 100;
+
+/// One item. This is the index of its kind in the world's `ItemRegistry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Item(pub u16);
+
+/// A stat that an item can raise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stat {
+  MaxHitPoints,
+  AttackStep,
+  DamageStep,
+  Defense,
+  Armor,
+  Sight,
+  Speed,
+}
+
+impl ::std::str::FromStr for Stat {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    match s {
+      "max_hit_points" => Ok(Stat::MaxHitPoints),
+      "attack_step" => Ok(Stat::AttackStep),
+      "damage_step" => Ok(Stat::DamageStep),
+      "defense" => Ok(Stat::Defense),
+      "armor" => Ok(Stat::Armor),
+      "sight" => Ok(Stat::Sight),
+      "speed" => Ok(Stat::Speed),
+      _ => Err(()),
+    }
+  }
+}
+
+/// One thing that happens when an item is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemEffect {
+  /// Heals each creature by a step roll, but not past the cap. The cap can be
+  /// higher than a creature's max hit points.
+  Heal { step: i32, cap: i32 },
+  /// Raises a stat of each creature for good.
+  Buff(Stat, i32),
+  /// Hurts each creature by a step roll.
+  Damage { step: i32 },
+  /// Turns floor into ice. Any items there are lost, but tiles with someone
+  /// standing on them stay as they are.
+  Freeze,
+  /// Turns ice into floor.
+  Thaw,
+  /// Sends each creature to a random spot on the level.
+  Teleport,
+  /// Puts a timed effect on each creature.
+  Status(StatusEffect),
+}
+
+impl ::std::str::FromStr for ItemEffect {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let num = |i: usize| words.get(i).and_then(|word| word.parse::<i32>().ok()).ok_or(());
+    match words.get(0) {
+      Some(&"heal") if words.len() == 3 => Ok(ItemEffect::Heal { step: num(1)?, cap: num(2)? }),
+      Some(&"buff") if words.len() == 3 => Ok(ItemEffect::Buff(words[1].parse()?, num(2)?)),
+      Some(&"damage") if words.len() == 2 => Ok(ItemEffect::Damage { step: num(1)? }),
+      Some(&"freeze") if words.len() == 1 => Ok(ItemEffect::Freeze),
+      Some(&"thaw") if words.len() == 1 => Ok(ItemEffect::Thaw),
+      Some(&"teleport") if words.len() == 1 => Ok(ItemEffect::Teleport),
+      Some(&"status") if words.len() == 3 || words.len() == 4 => Ok(ItemEffect::Status(StatusEffect {
+        kind: words[1].parse()?,
+        turns_left: num(2)?.max(1) as u32,
+        potency: if words.len() == 4 { num(3)? } else { 0 },
+      })),
+      _ => Err(()),
+    }
+  }
+}
+
+/// Everything about one kind of item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemDef {
+  pub name: String,
+  pub glyph: u8,
+  pub color: u32,
+  /// If the item gets thrown at a spot rather than used in place.
+  pub needs_target: bool,
+  /// How far from the target spot (or the user) the effects reach.
+  pub blast_radius: i32,
+  /// How loud it is when it goes off, 0 for no noise at all.
+  pub loudness: i32,
+  pub effects: Vec<ItemEffect>,
+  /// Where it goes if it's something to wear or hold.
+  pub slot: Option<EquipSlot>,
+  /// How much each stat goes up while it's equipped.
+  pub bonuses: Vec<(Stat, i32)>,
+  /// The shallowest and deepest levels this item spawns on (inclusive).
+  pub depths: (u32, u32),
+  pub rarity: u32,
+}
+
+impl ItemDef {
+  fn from_section(section: &DefSection) -> Result<Self, DefError> {
+    let mut effects = vec![];
+    if section.has("effects") {
+      for effect_text in section.get_str("effects")?.split(',') {
+        effects.push(effect_text.parse().map_err(|_| DefError {
+          line: section.line,
+          message: format!("[{}] has a bad effect {:?}", section.name, effect_text.trim()),
+        })?);
+      }
+    }
+    let mut bonuses = vec![];
+    if section.has("bonuses") {
+      for bonus_text in section.get_str("bonuses")?.split(',') {
+        let words: Vec<&str> = bonus_text.split_whitespace().collect();
+        let bonus = match words.as_slice() {
+          [stat, amount] => stat.parse().ok().and_then(|stat| amount.parse().ok().map(|amount| (stat, amount))),
+          _ => None,
+        };
+        bonuses.push(bonus.ok_or_else(|| DefError {
+          line: section.line,
+          message: format!("[{}] has a bad bonus {:?}", section.name, bonus_text.trim()),
+        })?);
+      }
+    }
+    let slot = if section.has("equip") { Some(section.get("equip")?) } else { None };
+    Ok(ItemDef {
+      name: section.name.clone(),
+      glyph: section.get_glyph("glyph")?,
+      color: section.get_color("color")?,
+      needs_target: section.get_or("needs_target", false)?,
+      blast_radius: section.get_or("blast_radius", 0)?,
+      loudness: section.get_or("loudness", 0)?,
+      effects,
+      slot,
+      bonuses,
+      depths: section.get_range("depth")?,
+      rarity: section.get_rarity("rarity")?,
+    })
+  }
+
+  pub fn spawns_at(&self, depth: u32) -> bool {
+    self.depths.0 <= depth && depth <= self.depths.1
+  }
+}
+
+/// All of the item kinds in the game.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ItemRegistry {
+  pub defs: Vec<ItemDef>,
+}
+
+impl ItemRegistry {
+  pub fn parse(text: &str) -> Result<Self, DefError> {
+    let mut defs = vec![];
+    for section in parse_def_sections(text)? {
+      defs.push(ItemDef::from_section(&section)?);
+    }
+    if defs.len() > u16::max_value() as usize {
+      return Err(DefError {
+        line: 0,
+        message: "too many kinds of item".to_string(),
+      });
+    }
+    Ok(ItemRegistry { defs })
+  }
+
+  /// The items from `data/items.txt`.
+  pub fn builtin() -> Self {
+    Self::parse(ITEM_DEFS_TEXT).unwrap_or_else(|e| panic!("bad data/items.txt, {}", e))
+  }
+
+  /// The definition of an item.
+  ///
+  /// Panics if the item isn't from this registry.
+  pub fn def(&self, item: Item) -> &ItemDef {
+    &self.defs[item.0 as usize]
+  }
+
+  pub fn by_name(&self, name: &str) -> Option<Item> {
+    self.defs.iter().position(|def| def.name == name).map(|i| Item(i as u16))
+  }
+
+  /// Picks an item for the depth given, with the common ones more likely.
+  pub fn pick_for_depth(&self, depth: u32, gen: &mut PCG32) -> Option<Item> {
+    // picked by index, so that two defs with the same name can't get mixed up.
+    let indexed: Vec<(usize, &ItemDef)> = self.defs.iter().enumerate().filter(|&(_, def)| def.spawns_at(depth)).collect();
+    pick_by_rarity(indexed.iter().collect(), |&(_, def)| def.rarity, gen).map(|&(i, _)| Item(i as u16))
+  }
+}
+
+impl GameWorld {
+  /// Does all of an item's effects, blasting out from the center given.
+  /// `user_id` is whoever used (or threw) it.
+  pub(crate) fn apply_item_effects(&mut self, item: Item, center: Location, user_id: CreatureID) {
+    let def = self.item_registry.def(item).clone();
+    let used_by_player = self.creatures.get(user_id).map(|user| user.is_the_player).unwrap_or(false);
+    let death_cause = match self.creatures.get(user_id) {
+      Some(user) if !user.is_the_player => DeathCause::Monster(user.name.clone()),
+      _ => DeathCause::OwnItem(def.name.clone()),
+    };
+    let mut blast_locations = vec![];
+    if def.blast_radius > 0 {
+      ppfov(
+        (center.x, center.y),
+        def.blast_radius,
+        |x, y| self.terrain.get(Location { x, y }) == Terrain::Wall,
+        |x, y| blast_locations.push(Location { x, y }),
+      );
+    } else {
+      blast_locations.push(center);
+    }
+    if def.loudness > 0 {
+      self.make_noise(center, def.loudness, NoiseKind::Explosion);
+    }
+    for effect in def.effects.iter() {
+      // effects go out in ID order, same as the rest of the turn.
+      let mut targets: Vec<CreatureID> = blast_locations.iter().filter_map(|&loc| self.creatures.id_at(loc)).collect();
+      targets.sort_by_key(|cid| cid.index);
+      match *effect {
+        ItemEffect::Heal { step: heal_step, cap } => {
+          for cid in targets.into_iter() {
+            let healing = step(&mut self.gen, heal_step);
+            let target = self.creatures.get_mut(cid).unwrap();
+            target.hit_points = (target.hit_points + healing).min(cap).max(target.hit_points);
+          }
+        }
+        ItemEffect::Buff(stat, amount) => {
+          for cid in targets.into_iter() {
+            self.creatures.get_mut(cid).unwrap().raise_stat(stat, amount);
+          }
+        }
+        ItemEffect::Damage { step: damage_step } => {
+          for cid in targets.into_iter() {
+            let damage = step(&mut self.gen, damage_step);
+            let target = self.creatures.get_mut(cid).unwrap();
+            target.hit_points -= damage;
+            if damage > 0 {
+              target.hurt_by_player = used_by_player;
+            }
+            self.messages.push(GameEvent::Blasted {
+              target: Who::of(target),
+              item_name: def.name.clone(),
+              damage,
+            });
+          }
+          self.check_player_death(|| death_cause.clone());
+        }
+        ItemEffect::Freeze => {
+          let mut frozen_locations = vec![];
+          for &location in blast_locations.iter() {
+            if self.terrain.get(location) == Terrain::Floor && self.creatures.id_at(location).is_none() {
+              self.terrain.set(location, Terrain::Ice);
+              frozen_locations.push(location);
+              self.item_locations.remove(&location);
+            }
+          }
+          if !frozen_locations.is_empty() {
+            self.mark_fov_dirty();
+            self.clear_flow_fields();
+            self.messages.push(GameEvent::TerrainChanged {
+              locations: frozen_locations,
+              from: Terrain::Floor,
+              to: Terrain::Ice,
+            });
+          }
+        }
+        ItemEffect::Thaw => {
+          let mut thawed_locations = vec![];
+          for &location in blast_locations.iter() {
+            if self.terrain.get(location) == Terrain::Ice {
+              self.terrain.set(location, Terrain::Floor);
+              thawed_locations.push(location);
+            }
+          }
+          if !thawed_locations.is_empty() {
+            self.mark_fov_dirty();
+            self.clear_flow_fields();
+            self.messages.push(GameEvent::TerrainChanged {
+              locations: thawed_locations,
+              from: Terrain::Ice,
+              to: Terrain::Floor,
+            });
+            self.make_noise(center, ICE_SHATTER_LOUDNESS, NoiseKind::IceShattering);
+          }
+        }
+        ItemEffect::Teleport => {
+          for cid in targets.into_iter() {
+            // on a packed level there might not be anywhere to go, and then
+            // the creature just stays put.
+            let mut destination = None;
+            for _ in 0..TELEPORT_TRIES {
+              let spot = self.pick_random_floor();
+              if self.creatures.id_at(spot).is_none() {
+                destination = Some(spot);
+                break;
+              }
+            }
+            let destination = match destination {
+              Some(destination) => destination,
+              None => continue,
+            };
+            self.creatures.move_to(cid, destination);
+            if self.creatures.player_id() == Some(cid) {
+              self.player_location = destination;
+              self.mark_fov_dirty();
+              self.clear_flow_fields();
+            }
+          }
+        }
+        ItemEffect::Status(effect) => {
+          for cid in targets.into_iter() {
+            self.add_status(cid, effect);
+          }
+        }
+      }
+    }
+  }
+}
+
+#[test]
+fn test_builtin_items() {
+  let registry = ItemRegistry::builtin();
+  let health = registry.by_name("Potion of Restore Health").unwrap();
+  assert_eq!(registry.def(health).effects, vec![ItemEffect::Heal { step: 8, cap: 30 }]);
+  let blast = registry.by_name("Blast Bomb").unwrap();
+  assert!(registry.def(blast).needs_target);
+  assert_eq!(registry.def(blast).effects, vec![ItemEffect::Thaw, ItemEffect::Damage { step: 10 }]);
+  assert_eq!("buff speed 5".parse(), Ok(ItemEffect::Buff(Stat::Speed, 5)));
+  assert!("buff luck 5".parse::<ItemEffect>().is_err());
+  assert!("heal 8".parse::<ItemEffect>().is_err());
+  let poison = StatusEffect {
+    kind: StatusKind::Poisoned,
+    turns_left: 4,
+    potency: 2,
+  };
+  assert_eq!("status poisoned 4 2".parse(), Ok(ItemEffect::Status(poison)));
+  assert!("status sleepy 4".parse::<ItemEffect>().is_err());
+  let spear = registry.by_name("Bronze Spear").unwrap();
+  assert_eq!(registry.def(spear).slot, Some(EquipSlot::Weapon));
+  assert_eq!(registry.def(spear).bonuses, vec![(Stat::DamageStep, 2)]);
+  assert!(ItemRegistry::parse("[Hat]\nglyph = ^\ncolor = 1 2 3\nequip = hat\ndepth = 1 1\nrarity = 1").is_err());
+}
+
+#[test]
+fn test_picks_go_by_index() {
+  // the data files can't have two items with the same name, but a registry
+  // that's put together by hand can.
+  let rock = ItemRegistry::parse("[Rock]\nglyph = *\ncolor = 1 2 3\ndepth = 1 1\nrarity = 1").unwrap().defs[0].clone();
+  let deep_rock = ItemDef { depths: (2, 2), ..rock.clone() };
+  let registry = ItemRegistry { defs: vec![rock, deep_rock] };
+  let mut gen = PCG32::new(3);
+  assert_eq!(registry.pick_for_depth(2, &mut gen), Some(Item(1)));
+  assert_eq!(registry.pick_for_depth(3, &mut gen), None);
+}
+
+#[test]
+fn test_teleport_gives_up_on_a_full_level() {
+  let mut world = GameWorld::new(31337);
+  let floors: Vec<Location> = world.terrain.iter().filter(|&(_, terrain)| terrain == Terrain::Floor).map(|(loc, _)| loc).collect();
+  for loc in floors.into_iter() {
+    world.creatures.spawn(loc, |id| Creature::new(id, b'r', 0));
+  }
+  let start = world.player_location;
+  let player_id = world.creatures.player_id().unwrap();
+  let scroll = world.item_registry.by_name("Scroll of Teleportation").unwrap();
+  world.apply_item_effects(scroll, start, player_id);
+  assert_eq!(world.player_location, start);
+  assert_eq!(world.creatures.location_of(player_id), Some(start));
+}
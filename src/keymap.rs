@@ -0,0 +1,217 @@
+//! Which keys do what, loaded from a file like `data/keys.txt`.
+//!
+//! Keys are just names here, so that this doesn't need to know about any
+//! particular window library. A frontend names its keys however its library
+//! does, and the file uses those same names.
+
+use super::*;
+
+/// The key bindings that are built into the game.
+pub const KEYMAP_DEFAULTS_TEXT: &str = include_str!("../data/keys.txt");
+
+/// Where the player's own key bindings are kept.
+pub const KEYMAP_FILE_NAME: &str = "kasidin-keys.txt";
+
+/// Which set of key bindings applies. There's one for each kind of
+/// `DisplayMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyContext {
+  Game,
+  Inventory,
+  Targeting,
+  Look,
+  GameOver,
+}
+
+impl KeyContext {
+  pub fn of(display_mode: DisplayMode) -> Self {
+    match display_mode {
+      DisplayMode::Game => KeyContext::Game,
+      DisplayMode::Inventory => KeyContext::Inventory,
+      DisplayMode::ItemTargeting(..) => KeyContext::Targeting,
+      DisplayMode::Look(..) => KeyContext::Look,
+      DisplayMode::GameOver => KeyContext::GameOver,
+    }
+  }
+
+  /// If the driver does anything with a command in this context. `Quit` is
+  /// up to the frontend, so it's allowed anywhere.
+  pub fn handles(self, command: Command) -> bool {
+    if command == Command::Quit {
+      return true;
+    }
+    match self {
+      KeyContext::Game => match command {
+        Command::Move(..)
+        | Command::Rest
+        | Command::OpenInventory
+        | Command::Look
+        | Command::UseStairs
+        | Command::OlderMessages
+        | Command::NewerMessages => true,
+        _ => false,
+      },
+      KeyContext::Inventory => match command {
+        Command::Cancel | Command::SelectItem(..) | Command::Unequip(..) => true,
+        _ => false,
+      },
+      KeyContext::Targeting => match command {
+        Command::Cancel | Command::Confirm | Command::Move(..) => true,
+        _ => false,
+      },
+      KeyContext::Look => match command {
+        Command::Cancel | Command::Move(..) => true,
+        _ => false,
+      },
+      KeyContext::GameOver => false,
+    }
+  }
+
+  fn from_section_name(name: &str) -> Option<Self> {
+    match name {
+      "game" => Some(KeyContext::Game),
+      "inventory" => Some(KeyContext::Inventory),
+      "targeting" => Some(KeyContext::Targeting),
+      "look" => Some(KeyContext::Look),
+      "game_over" => Some(KeyContext::GameOver),
+      _ => None,
+    }
+  }
+}
+
+/// Reads the text of an action, such as `move_north` or `select_item a`.
+fn parse_command(text: &str) -> Option<Command> {
+  let words: Vec<&str> = text.split_whitespace().collect();
+  let direction = |name: &str| match name {
+    "north" => Some(Location { x: 0, y: 1 }),
+    "south" => Some(Location { x: 0, y: -1 }),
+    "east" => Some(Location { x: 1, y: 0 }),
+    "west" => Some(Location { x: -1, y: 0 }),
+    "northeast" => Some(Location { x: 1, y: 1 }),
+    "northwest" => Some(Location { x: -1, y: 1 }),
+    "southeast" => Some(Location { x: 1, y: -1 }),
+    "southwest" => Some(Location { x: -1, y: -1 }),
+    _ => None,
+  };
+  match words.as_slice() {
+    [word] if word.starts_with("move_") => direction(&word["move_".len()..]).map(Command::Move),
+    ["rest"] => Some(Command::Rest),
+    ["open_inventory"] => Some(Command::OpenInventory),
+    ["look"] => Some(Command::Look),
+    ["use_stairs"] => Some(Command::UseStairs),
+    ["older_messages"] => Some(Command::OlderMessages),
+    ["newer_messages"] => Some(Command::NewerMessages),
+    ["select_item", letter] => match letter.as_bytes() {
+      [byte] if byte.is_ascii_lowercase() => Some(Command::SelectItem(*byte as char)),
+      _ => None,
+    },
+    ["unequip", slot] => slot.parse().ok().map(Command::Unequip),
+    ["confirm"] => Some(Command::Confirm),
+    ["cancel"] => Some(Command::Cancel),
+    ["quit"] => Some(Command::Quit),
+    _ => None,
+  }
+}
+
+/// Every key binding, for every `KeyContext`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keymap {
+  /// Key names are kept in lower case.
+  bindings: HashMap<(KeyContext, String), Command>,
+}
+
+impl Keymap {
+  /// Reads key bindings. A key that's bound twice in the same section (even
+  /// with different upper and lower case) is an error, and so is an action
+  /// that does nothing in its section.
+  pub fn parse(text: &str) -> Result<Self, DefError> {
+    let mut bindings = HashMap::new();
+    for section in parse_def_sections(text)? {
+      let context = KeyContext::from_section_name(&section.name).ok_or_else(|| DefError {
+        line: section.line,
+        message: format!("[{}] isn't a kind of screen", section.name),
+      })?;
+      for &(ref key, ref action, line) in section.fields.iter() {
+        let command = parse_command(action).ok_or_else(|| DefError {
+          line,
+          message: format!("`{}` isn't an action", action),
+        })?;
+        if !context.handles(command) {
+          return Err(DefError {
+            line,
+            message: format!("`{}` doesn't do anything in [{}]", action, section.name),
+          });
+        }
+        let key_name = key.to_lowercase();
+        if bindings.contains_key(&(context, key_name.clone())) {
+          return Err(DefError {
+            line,
+            message: format!("`{}` is bound twice in [{}]", key, section.name),
+          });
+        }
+        bindings.insert((context, key_name), command);
+      }
+    }
+    Ok(Keymap { bindings })
+  }
+
+  /// The bindings from `data/keys.txt`.
+  pub fn builtin() -> Self {
+    Self::parse(KEYMAP_DEFAULTS_TEXT).unwrap_or_else(|e| panic!("bad data/keys.txt, {}", e))
+  }
+
+  /// Loads the player's key bindings from a file. If they don't have one yet
+  /// the defaults get written out so that they have a file to edit. Anything
+  /// that goes wrong means using the defaults, with a note saying why.
+  pub fn load_or_create<P: AsRef<::std::path::Path>>(path: P) -> (Self, Option<String>) {
+    let path = path.as_ref();
+    match ::std::fs::read_to_string(path) {
+      Ok(text) => match Self::parse(&text) {
+        Ok(keymap) => (keymap, None),
+        Err(e) => (
+          Self::builtin(),
+          Some(format!("couldn't use the key bindings in {}, using the defaults: {}", path.display(), e)),
+        ),
+      },
+      Err(_) => match ::std::fs::write(path, KEYMAP_DEFAULTS_TEXT) {
+        Ok(()) => (Self::builtin(), None),
+        Err(e) => (Self::builtin(), Some(format!("couldn't write the default key bindings: {}", e))),
+      },
+    }
+  }
+
+  /// What a key does in the display mode given, if anything.
+  pub fn command_for(&self, display_mode: DisplayMode, key_name: &str) -> Option<Command> {
+    self.bindings.get(&(KeyContext::of(display_mode), key_name.to_lowercase())).cloned()
+  }
+}
+
+#[test]
+fn test_keymap() {
+  let keymap = Keymap::builtin();
+  assert_eq!(keymap.command_for(DisplayMode::Game, "K"), Some(Command::Move(Location { x: 0, y: 1 })));
+  assert_eq!(keymap.command_for(DisplayMode::Game, "Numpad5"), Some(Command::Rest));
+  assert_eq!(keymap.command_for(DisplayMode::Inventory, "Q"), Some(Command::SelectItem('q')));
+  assert_eq!(
+    keymap.command_for(DisplayMode::Inventory, "Key2"),
+    Some(Command::Unequip(EquipSlot::Armor))
+  );
+  let targeting = DisplayMode::ItemTargeting('a', Location { x: 0, y: 0 });
+  assert_eq!(keymap.command_for(targeting, "Return"), Some(Command::Confirm));
+  assert_eq!(keymap.command_for(DisplayMode::Game, "X"), Some(Command::Look));
+  assert_eq!(keymap.command_for(DisplayMode::Look(Location { x: 0, y: 0 }), "X"), Some(Command::Cancel));
+  assert_eq!(keymap.command_for(DisplayMode::GameOver, "K"), None);
+
+  let rebound = Keymap::parse("[game]\nW = move_north\nz = rest").unwrap();
+  assert_eq!(rebound.command_for(DisplayMode::Game, "w"), Some(Command::Move(Location { x: 0, y: 1 })));
+  assert_eq!(rebound.command_for(DisplayMode::Game, "K"), None);
+
+  let conflict = Keymap::parse("[game]\nk = move_north\nK = rest").unwrap_err();
+  assert_eq!(conflict.line, 3);
+  assert!(Keymap::parse("[game]\nk = fly_north").is_err());
+  assert!(Keymap::parse("[shop]\nk = cancel").is_err());
+  let useless = Keymap::parse("[game]\nz = select_item z").unwrap_err();
+  assert_eq!(useless.line, 2);
+  assert!(Keymap::parse("[inventory]\nk = move_north").is_err());
+  assert!(Keymap::parse("[game_over]\nescape = quit").is_ok());
+}
@@ -9,57 +9,79 @@ pub(crate) use std::collections::hash_map::*;
 pub(crate) use std::collections::hash_set::*;
 pub(crate) use std::collections::BTreeMap;
 pub(crate) use std::ops::*;
-pub(crate) use std::sync::atomic::*;
 
+pub mod ai;
+pub use ai::*;
+pub mod combat;
+pub use combat::*;
+pub mod creatures;
+pub use creatures::*;
+pub mod defs;
+pub use defs::*;
+pub mod dijkstra;
+pub use dijkstra::*;
+pub mod driver;
+pub use driver::*;
+pub mod dungeon;
+pub use dungeon::*;
+pub mod equipment;
+pub use equipment::*;
+pub mod items;
+pub use items::*;
+pub mod keymap;
+pub use keymap::*;
+pub mod line_of_fire;
+pub use line_of_fire::*;
+pub mod look;
+pub use look::*;
+pub mod map_memory;
+pub use map_memory::*;
+pub mod messages;
+pub use messages::*;
+pub mod monsters;
+pub use monsters::*;
+pub mod morgue;
+pub use morgue::*;
+pub mod noise;
+pub use noise::*;
 pub mod pathing;
 pub use pathing::*;
 pub mod precise_permissive_fov;
 pub use precise_permissive_fov::*;
 pub mod prng;
 pub use prng::*;
+pub mod render;
+pub use render::*;
+pub mod replay;
+pub use replay::*;
+pub mod save;
+pub use save::*;
+pub mod scheduler;
+pub use scheduler::*;
+pub mod status;
+pub use status::*;
+pub mod terrain_map;
+pub use terrain_map::*;
+#[cfg(test)]
+mod test_support;
 
 pub const WALL_TILE: u8 = 11 + 13 * 16;
 pub const POTION_GLYPH: u8 = 13 + 10 * 16;
 pub const BOMB_GLYPH: u8 = 15 + 0 * 16;
 
 pub const TERULO_BROWN: u32 = rgb32!(197, 139, 5);
-pub const KESTREL_RED: u32 = rgb32!(166, 0, 0);
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum Item {
-  PotionHealth,
-  PotionStrength,
-  BombBlast,
-  BombIce,
-}
 
-impl Item {
-  fn is_potion(self) -> bool {
-    match self {
-      Item::PotionHealth | Item::PotionStrength => true,
-      _ => false,
-    }
-  }
-}
-
-fn apply_potion(potion: &Item, target: &mut Creature, rng: &mut PCG32) {
-  match potion {
-    Item::PotionHealth => target.hit_points = (target.hit_points + step(rng, 8)).min(30),
-    Item::PotionStrength => target.damage_step += 1,
-    _ => panic!("not a potion {}", potion),
-  }
-}
-
-impl ::std::fmt::Display for Item {
-  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-    match self {
-      Item::PotionHealth => write!(f, "Potion of Restore Health"),
-      Item::PotionStrength => write!(f, "Potion of Gain Strength"),
-      Item::BombBlast => write!(f, "Blast Bomb"),
-      Item::BombIce => write!(f, "Ice Bomb"),
-    }
-  }
-}
+/// One step in each of the 8 directions, the orthogonal ones first.
+pub const EIGHT_WAY_DELTAS: [Location; 8] = [
+  Location { x: 1, y: 0 },
+  Location { x: -1, y: 0 },
+  Location { x: 0, y: 1 },
+  Location { x: 0, y: -1 },
+  Location { x: 1, y: 1 },
+  Location { x: -1, y: 1 },
+  Location { x: 1, y: -1 },
+  Location { x: -1, y: -1 },
+];
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Hash)]
 pub struct Location {
@@ -113,6 +135,18 @@ impl Location {
       index: 0,
     }
   }
+
+  /// All 8 of the locations around this one, the orthogonal ones first.
+  pub fn neighbors_8(&self) -> impl Iterator<Item = Location> {
+    let here = *self;
+    EIGHT_WAY_DELTAS.iter().map(move |&delta| here + delta)
+  }
+}
+
+/// How many steps it takes to get from one spot to another if nothing is in
+/// the way. Diagonal steps count as 1, same as any other step.
+pub fn steps_between(a: Location, b: Location) -> i32 {
+  (a.x - b.x).abs().max((a.y - b.y).abs())
 }
 
 impl Add for Location {
@@ -137,39 +171,81 @@ impl Sub for Location {
 
 #[derive(Debug)]
 pub struct Creature {
+  /// The kind of creature, such as the name of its `MonsterDef`.
+  pub name: String,
   pub icon: u8,
   pub color: u32,
   pub is_the_player: bool,
   pub id: CreatureID,
   pub hit_points: i32,
+  pub max_hit_points: i32,
+  /// The step rolled to see if an attack hits.
+  pub attack_step: i32,
   pub damage_step: i32,
+  /// An attack roll has to be at least this much to hit it.
+  pub defense: i32,
+  /// Taken off of the damage of every hit it takes, except critical hits.
+  pub armor: i32,
+  /// How far away it can see, in tiles.
+  pub sight: i32,
+  /// How quickly it acts, 10 is normal. This is how much energy it gets
+  /// each tick.
+  pub speed: i32,
+  /// It gets to act once this is at least 0.
+  pub energy: i32,
+  pub ai: AiKind,
+  /// Where it last saw the player, if it's still looking for them.
+  pub memory: Option<PlayerMemory>,
+  /// How many of its turns it keeps looking after losing sight of the player.
+  pub memory_span: u32,
+  /// Where it heard a noise that it's going to go look at, if any.
+  pub investigating: Option<Location>,
+  /// If the last damage that it took (other than poison) came from the
+  /// player, so that they get the credit if it dies.
+  pub hurt_by_player: bool,
   pub inventory: Vec<Item>,
+  /// What it's wearing or holding. These aren't in `inventory`.
+  pub equipment: BTreeMap<EquipSlot, Item>,
+  /// Timed effects that it's under, at most one of each kind.
+  pub statuses: Vec<StatusEffect>,
 }
 impl Creature {
-  fn new(icon: u8, color: u32) -> Self {
+  fn new(id: CreatureID, icon: u8, color: u32) -> Self {
     Creature {
+      name: "creature".to_string(),
       icon,
       color,
       is_the_player: false,
-      id: CreatureID::atomic_new(),
+      id,
       hit_points: 1,
+      max_hit_points: 1,
+      attack_step: 5,
       damage_step: 1,
+      defense: 3,
+      armor: 0,
+      sight: 7,
+      speed: 10,
+      energy: 0,
+      ai: AiKind::Chaser,
+      memory: None,
+      memory_span: DEFAULT_MEMORY_SPAN,
+      investigating: None,
+      hurt_by_player: false,
       inventory: vec![],
+      equipment: BTreeMap::new(),
+      statuses: vec![],
     }
   }
 
-  fn new_player() -> Self {
-    let mut out = Self::new(b'@', TERULO_BROWN);
+  fn new_player(id: CreatureID) -> Self {
+    let mut out = Self::new(id, b'@', TERULO_BROWN);
+    out.name = "player".to_string();
     out.is_the_player = true;
     out.hit_points = 20;
+    out.max_hit_points = 20;
+    out.attack_step = 6;
     out.damage_step = 5;
-    out
-  }
-
-  fn new_kestrel() -> Self {
-    let mut out = Self::new(b'k', KESTREL_RED);
-    out.hit_points = 8;
-    out.damage_step = 3;
+    out.defense = 4;
     out
   }
 }
@@ -179,6 +255,8 @@ pub enum Terrain {
   Wall,
   Floor,
   Ice,
+  StairsDown,
+  StairsUp,
 }
 
 impl Default for Terrain {
@@ -187,7 +265,17 @@ impl Default for Terrain {
   }
 }
 
-fn make_cellular_caves(width: usize, height: usize, gen: &mut PCG32) -> VecImage<bool> {
+impl Terrain {
+  /// If creatures can stand here.
+  pub fn is_walkable(self) -> bool {
+    match self {
+      Terrain::Wall | Terrain::Ice => false,
+      Terrain::Floor | Terrain::StairsDown | Terrain::StairsUp => true,
+    }
+  }
+}
+
+fn make_cellular_caves(width: usize, height: usize, fill_percent: u32, gen: &mut PCG32) -> VecImage<bool> {
   // utilities
   let range_count = |buf: &VecImage<bool>, x: usize, y: usize, range: u32| {
     debug_assert!(range > 0);
@@ -272,9 +360,9 @@ fn make_cellular_caves(width: usize, height: usize, gen: &mut PCG32) -> VecImage
   let mut buffer_b: VecImage<bool> = VecImage::new(width, height);
 
   'work: loop {
-    // fill the initial buffer, all cells 45% likely.
+    // fill the initial buffer, all cells fill_percent likely.
     for (_x, _y, mut_ref) in buffer_a.iter_mut() {
-      *mut_ref = d100.roll_with(gen) <= 45;
+      *mut_ref = d100.roll_with(gen) <= fill_percent;
     }
     // cave copy from A into B, then the reverse, 5 times total
     cave_copy(&buffer_a, &mut buffer_b);
@@ -292,98 +380,100 @@ fn make_cellular_caves(width: usize, height: usize, gen: &mut PCG32) -> VecImage
   }
 }
 
-// we're setting aside '0' for a "null" type value, so the initial next value
-// starts at 1.
-static NEXT_CREATURE_ID: AtomicUsize = AtomicUsize::new(1);
-
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub struct CreatureID(pub usize);
-
-impl CreatureID {
-  fn atomic_new() -> Self {
-    CreatureID(NEXT_CREATURE_ID.fetch_add(1, Ordering::SeqCst))
-  }
-}
-
 #[derive(Debug, Default)]
 pub struct GameWorld {
   pub player_location: Location,
-  pub creature_list: Vec<Creature>,
-  pub creature_locations: HashMap<Location, CreatureID>,
+  pub creatures: CreatureStore,
   pub item_locations: HashMap<Location, Vec<Item>>,
-  pub terrain: HashMap<Location, Terrain>,
+  pub terrain: TerrainMap,
   pub gen: PCG32,
+  /// How far down the current level is, starting from 1.
+  pub depth: u32,
+  /// Every level that's been visited other than the current one, by depth.
+  pub other_levels: BTreeMap<u32, Level>,
+  /// The kinds of monster that new levels get filled with.
+  pub monster_registry: MonsterRegistry,
+  /// The kinds of item, which every `Item` in the world is an index into.
+  pub item_registry: ItemRegistry,
+  /// Everything that's happened lately, for the player to read.
+  pub messages: MessageLog,
+  /// The seed that the world was made from.
+  pub seed: u64,
+  /// How many scheduler ticks have gone by, see `turn_count`.
+  pub tick_count: u64,
+  /// How many of each kind of monster the player has killed.
+  pub kills: BTreeMap<String, u32>,
+  /// Set once the player dies.
+  pub game_over: Option<GameOver>,
+  /// AIs that creatures use in place of their own, see `set_ai`. This only
+  /// exists in tests, since it can't be saved.
+  #[cfg(test)]
+  pub(crate) ai_overrides: HashMap<CreatureID, Box<dyn Ai>>,
+  /// The Dijkstra maps for this world turn, see `toward_player_map`.
+  pub flow_fields: ::std::cell::RefCell<FlowFields>,
+  /// What the player has seen of the current level.
+  pub map_memory: MapMemory,
+  /// What the player can see right now, as of the last `update_fov`.
+  pub visible: HashSet<Location>,
+  /// If `visible` needs to be worked out again.
+  pub(crate) fov_dirty: bool,
+  /// How far the player can see, see `set_fov_range`.
+  pub fov_range: i32,
 }
 
 impl GameWorld {
   pub fn new(seed: u64) -> Self {
     let mut out = Self {
       player_location: Location { x: 5, y: 5 },
-      creature_list: vec![],
-      creature_locations: HashMap::new(),
+      creatures: CreatureStore::new(),
       item_locations: HashMap::new(),
-      terrain: HashMap::new(),
+      terrain: TerrainMap::default(),
       gen: PCG32::new(seed),
+      depth: 1,
+      other_levels: BTreeMap::new(),
+      monster_registry: MonsterRegistry::builtin(),
+      item_registry: ItemRegistry::builtin(),
+      messages: MessageLog::new(),
+      seed,
+      tick_count: 0,
+      kills: BTreeMap::new(),
+      game_over: None,
+      #[cfg(test)]
+      ai_overrides: HashMap::new(),
+      flow_fields: Default::default(),
+      map_memory: MapMemory::default(),
+      visible: HashSet::new(),
+      fov_dirty: true,
+      fov_range: DEFAULT_FOV_RANGE,
     };
-    let caves = make_cellular_caves(100, 100, &mut out.gen);
-    for (x, y, tile) in caves.iter() {
-      out
-        .terrain
-        .insert(Location { x: x as i32, y: y as i32 }, if *tile { Terrain::Wall } else { Terrain::Floor });
-    }
-
-    // add the player
-    let mut player = Creature::new_player();
-    let player_start = out.pick_random_floor();
-    let player_id = player.id.0;
-    out.creature_list.push(player);
-    out.creature_locations.insert(player_start, CreatureID(player_id));
-    out.player_location = player_start;
-
-    // add the enemies
-    for _ in 0..50 {
-      let monster = Creature::new_kestrel();
-      let monster_id = monster.id.0;
-      let monster_start = out.pick_random_floor();
-      match out.creature_locations.entry(monster_start) {
-        Entry::Occupied(_) => {
-          // if we happen to pick an occupied location, just don't add a
-          // creature for this pass of the loop.
-          continue;
-        }
-        Entry::Vacant(ve) => {
-          out.creature_list.push(monster);
-          ve.insert(CreatureID(monster_id));
-        }
+    out.generate_level(1);
+
+    // add the player. The monsters are already out, so we might need a few
+    // tries to find an empty spot.
+    loop {
+      let player_start = out.pick_random_floor();
+      if out.creatures.spawn(player_start, Creature::new_player).is_some() {
+        out.player_location = player_start;
+        break;
       }
     }
-
-    // add some items
-    for _ in 0..100 {
-      let item_spot = out.pick_random_floor();
-      let new_item = match out.gen.next_u32() >> 30 {
-        0 => Item::PotionHealth,
-        1 => Item::PotionStrength,
-        2 => Item::BombBlast,
-        3 => Item::BombIce,
-        _ => unreachable!(),
-      };
-      out.item_locations.entry(item_spot).or_insert(Vec::new()).push(new_item);
-    }
+    out.update_fov();
 
     out
   }
 
   pub fn pick_random_floor(&mut self) -> Location {
-    let indexer = RandRangeInclusive32::new(0..=99);
+    let x_indexer = RandRangeInclusive32::new(0..=(self.terrain.width() as u32 - 1));
+    let y_indexer = RandRangeInclusive32::new(0..=(self.terrain.height() as u32 - 1));
     let mut tries = 0;
-    let mut x = indexer.roll_with(&mut self.gen) as usize;
-    let mut y = indexer.roll_with(&mut self.gen) as usize;
+    let mut x = x_indexer.roll_with(&mut self.gen) as usize;
+    let mut y = y_indexer.roll_with(&mut self.gen) as usize;
     let mut loc = Location { x: x as i32, y: y as i32 };
-    while self.terrain[&loc] != Terrain::Floor {
-      x = indexer.roll_with(&mut self.gen) as usize;
-      y = indexer.roll_with(&mut self.gen) as usize;
+    while self.terrain.get(loc) != Terrain::Floor {
+      x = x_indexer.roll_with(&mut self.gen) as usize;
+      y = y_indexer.roll_with(&mut self.gen) as usize;
       loc = Location { x: x as i32, y: y as i32 };
+      tries += 1;
       if tries > 5000 {
         panic!("couldn't find a floor tile!");
       }
@@ -392,272 +482,232 @@ impl GameWorld {
   }
 
   pub fn move_player(&mut self, delta: Location) {
+    if self.game_over.is_some() {
+      return;
+    }
     let player_move_target = self.player_location + delta;
-    match self.creature_locations.get(&player_move_target) {
-      Some(target_id_ref) => {
+    let action_cost = match self.creatures.id_at(player_move_target) {
+      Some(target_id) => {
         // someone is there, do the attack!
-        let player_damage_roll = {
-          let player_id_ref = self.creature_locations.get(&self.player_location).unwrap();
-          let player_ref = self.creature_list.iter().find(|creature_ref| &creature_ref.id == player_id_ref).unwrap();
-          step(&mut self.gen, player_ref.damage_step)
-        };
-        let target_ref_mut = self
-          .creature_list
-          .iter_mut()
-          .find(|creature_mut_ref| &creature_mut_ref.id == target_id_ref)
-          .unwrap();
-        target_ref_mut.hit_points -= player_damage_roll;
-        println!("Player did {} damage to {:?}", player_damage_roll, target_id_ref);
+        let player_id = self.creatures.player_id().expect("There's no player!");
+        self.perform_attack(player_id, target_id, false);
+        ATTACK_COST
       }
       None => {
         // no one is there, move
-        match *self.terrain.entry(player_move_target).or_insert(Terrain::Floor) {
+        match self.terrain.get(player_move_target) {
           Terrain::Wall | Terrain::Ice => {
             // Accidentally bumping a wall doesn't consume a turn.
             return;
           }
-          Terrain::Floor => {
-            let player_id = self
-              .creature_locations
-              .remove(&self.player_location)
-              .expect("The player wasn't where they should be!");
-            let old_creature = self.creature_locations.insert(player_move_target, player_id);
-            debug_assert!(old_creature.is_none());
+          Terrain::Floor | Terrain::StairsDown | Terrain::StairsUp => {
+            let player_id = self.creatures.player_id().expect("There's no player!");
+            let moved = self.creatures.move_to(player_id, player_move_target);
+            debug_assert!(moved);
             self.player_location = player_move_target;
+            self.mark_fov_dirty();
+            // the player moved, and maybe picked things up.
+            self.clear_flow_fields();
             // grab items that are here, if any
-            let player_id_ref = self.creature_locations.get(&self.player_location).unwrap();
-            let player_mut = self
-              .creature_list
-              .iter_mut()
-              .find(|creature_mut| &creature_mut.id == player_id_ref)
-              .unwrap();
+            let player_mut = self.creatures.player_mut().unwrap();
             let floor_items = self.item_locations.entry(self.player_location).or_insert(Vec::new());
+            for &item in floor_items.iter() {
+              self.messages.push(GameEvent::PickedUp {
+                who: Who::of(player_mut),
+                item_name: self.item_registry.def(item).name.clone(),
+              });
+            }
             player_mut.inventory.append(floor_items);
           }
         }
+        MOVE_COST
       }
+    };
+    self.run_world_turn(action_cost);
+  }
+
+  /// Stands still for a turn.
+  pub fn rest(&mut self) {
+    if self.game_over.is_some() {
+      return;
     }
-    self.run_world_turn();
-    println!("turn over!");
+    self.run_world_turn(WAIT_COST);
   }
 
-  pub fn use_item(&mut self, item_letter: char) -> UseItemResult {
-    let player_mut = self.creature_list.iter_mut().find(|creature_ref| creature_ref.is_the_player).unwrap();
-    let item_to_use = {
-      let mut cataloged_inventory = BTreeMap::new();
-      for item_ref in player_mut.inventory.iter() {
-        *cataloged_inventory.entry(item_ref).or_insert(0) += 1;
+  /// The item that's listed under this letter in the player's inventory.
+  fn player_item_by_letter(&self, item_letter: char) -> Option<Item> {
+    let player_ref = self.creatures.player().unwrap();
+    let mut cataloged_inventory = BTreeMap::new();
+    for item_ref in player_ref.inventory.iter() {
+      *cataloged_inventory.entry(item_ref).or_insert(0) += 1;
+    }
+    let letter_index = (item_letter as u8).wrapping_sub(b'a');
+    cataloged_inventory.into_iter().nth(letter_index as usize).map(|(&item, _count)| item)
+  }
+
+  fn remove_player_item(&mut self, item: Item) {
+    let player_mut = self.creatures.player_mut().unwrap();
+    for i in 0..player_mut.inventory.len() {
+      if player_mut.inventory[i] == item {
+        player_mut.inventory.remove(i);
+        break;
       }
-      let letter_index = item_letter as u8 - 'a' as u8;
-      cataloged_inventory.into_iter().nth(letter_index as usize).map(|(&item, _count)| item)
-    };
-    match item_to_use {
+    }
+  }
+
+  fn note_item_used(&mut self, item: Item) {
+    self.messages.push(GameEvent::ItemUsed {
+      who: Who::of(self.creatures.player().unwrap()),
+      item_name: self.item_registry.def(item).name.clone(),
+    });
+  }
+
+  pub fn use_item(&mut self, item_letter: char) -> UseItemResult {
+    if self.game_over.is_some() {
+      return UseItemResult::NoSuchItem;
+    }
+    match self.player_item_by_letter(item_letter) {
       Some(item) => {
-        if item.is_potion() {
-          apply_potion(&item, player_mut, &mut self.gen);
-          for i in 0..player_mut.inventory.len() {
-            if player_mut.inventory[i] == item {
-              player_mut.inventory.remove(i);
-              break;
-            }
-          }
-          self.run_world_turn();
+        if self.item_registry.def(item).slot.is_some() {
+          self.player_equip(item);
           UseItemResult::ItemUsed
-        } else {
+        } else if self.item_registry.def(item).needs_target {
           UseItemResult::ItemNeedsTarget
+        } else {
+          let (user_id, user_location) = (self.creatures.player_id().unwrap(), self.player_location);
+          self.note_item_used(item);
+          self.apply_item_effects(item, user_location, user_id);
+          self.remove_player_item(item);
+          self.run_world_turn(USE_ITEM_COST);
+          UseItemResult::ItemUsed
         }
       }
       None => UseItemResult::NoSuchItem,
     }
   }
 
-  pub fn use_targeted_item(&mut self, item_letter: char, target_delta: Location) {
-    let item_to_use = {
-      let player_mut = self.creature_list.iter_mut().find(|creature_ref| creature_ref.is_the_player).unwrap();
-      let mut cataloged_inventory = BTreeMap::new();
-      for item_ref in player_mut.inventory.iter() {
-        *cataloged_inventory.entry(item_ref).or_insert(0) += 1;
-      }
-      let letter_index = item_letter as u8 - 'a' as u8;
-      cataloged_inventory.into_iter().nth(letter_index as usize).map(|(&item, _count)| item)
+  /// Has the player throw an item at a spot, using up a turn.
+  ///
+  /// Gives `false` (and doesn't use up a turn) if there's no such item, if
+  /// it isn't something to throw, or if it would only land back on the
+  /// player, such as when they're right up against a wall.
+  pub fn use_targeted_item(&mut self, item_letter: char, target_delta: Location) -> bool {
+    if self.game_over.is_some() {
+      return false;
+    }
+    let item = match self.player_item_by_letter(item_letter) {
+      Some(item) if self.item_registry.def(item).needs_target => item,
+      _ => return false,
     };
+    let (player_id, target) = (self.creatures.player_id().unwrap(), self.player_location + target_delta);
+    if self.trace_projectile(self.player_location, target).impact == self.player_location {
+      self.messages.push(GameEvent::NoRoomToThrow {
+        item_name: self.item_registry.def(item).name.clone(),
+      });
+      return false;
+    }
+    self.throw_item(player_id, item, target);
+    self.run_world_turn(USE_ITEM_COST);
+    true
+  }
 
-    match item_to_use {
-      Some(Item::BombBlast) => {
-        let mut blast_locations = vec![];
-        let blast_center = self.player_location + target_delta;
+  /// Lets one monster do whatever it's going to do, giving the energy cost of
+  /// what it did.
+  fn take_monster_turn(&mut self, creature_id: CreatureID) -> i32 {
+    let loc = match self.creatures.location_of(creature_id) {
+      None => unreachable!("Creature {:?} is not anywhere!", creature_id),
+      Some(loc) => loc,
+    };
+    // Look around
+    let sight = self.creatures.get(creature_id).unwrap().sight;
+    let seen_locations = {
+      let terrain_ref = &self.terrain;
+      let mut seen_locations = HashSet::new();
+      if sight > 0 {
         ppfov(
-          (blast_center.x, blast_center.y),
-          2,
-          |x, y| self.terrain[&Location { x, y }] == Terrain::Wall,
-          |x, y| blast_locations.push(Location { x, y }),
+          (loc.x, loc.y),
+          sight,
+          |x, y| terrain_ref.blocks_sight(Location { x, y }),
+          |x, y| {
+            seen_locations.insert(Location { x, y });
+          },
         );
-        let mut blast_targets = vec![];
-        for location in blast_locations.into_iter() {
-          if *self.terrain.entry(location).or_insert(Terrain::Wall) == Terrain::Ice {
-            *self.terrain.entry(location).or_insert(Terrain::Wall) = Terrain::Floor;
+      }
+      seen_locations
+    };
+    // Decide what to do, then do it
+    match self.choose_monster_action(creature_id, &seen_locations) {
+      MonsterAction::Step(delta) => {
+        let move_target = loc + delta;
+        match self.creatures.id_at(move_target) {
+          Some(target_id) => {
+            // someone is there, do the attack!
+            self.monster_attack(creature_id, target_id, false);
+            ATTACK_COST
           }
-          match self.creature_locations.get(&location) {
-            None => {}
-            Some(cid_ref) => {
-              blast_targets.push(CreatureID(cid_ref.0));
+          None => {
+            match self.terrain.get(move_target) {
+              // bumping into a wall still wastes the turn
+              Terrain::Wall | Terrain::Ice => {}
+              Terrain::Floor | Terrain::StairsDown | Terrain::StairsUp => {
+                let moved = self.creatures.move_to(creature_id, move_target);
+                debug_assert!(moved);
+              }
             }
+            MOVE_COST
           }
         }
-        for creature_mut in self.creature_list.iter_mut() {
-          if blast_targets.contains(&creature_mut.id) {
-            creature_mut.hit_points -= step(&mut self.gen, 10);
+      }
+      MonsterAction::Shoot(target) => {
+        if seen_locations.contains(&target) {
+          // the shot hits whoever is in the way, not always who it was aimed at.
+          if let Some(target_id) = self.trace_projectile(loc, target).struck {
+            self.monster_attack(creature_id, target_id, true);
           }
         }
+        ATTACK_COST
       }
-      Some(Item::BombIce) => {
-        let mut blast_locations = vec![];
-        let blast_center = self.player_location + target_delta;
-        ppfov(
-          (blast_center.x, blast_center.y),
-          1,
-          |_, _| false, /* vision check doesn't matter on radius 1 fov */
-          |x, y| blast_locations.push(Location { x, y }),
-        );
-        for location in blast_locations.into_iter() {
-          if *self.terrain.entry(location).or_insert(Terrain::Wall) == Terrain::Floor {
-            *self.terrain.entry(location).or_insert(Terrain::Wall) = Terrain::Ice;
-            self.item_locations.entry(location).or_insert(Vec::new()).clear();
-            let removed_cid = self.creature_locations.remove(&location);
-            // this is a hacky way to never delete the player on accident, but
-            // not really any _more_ hacky than the rest of the codebase.
-            removed_cid.map(|cid_ref| {
-              if cid_ref.0 > 1 {
-                self.creature_list.retain(|creature_ref| &creature_ref.id != &cid_ref);
-              } else {
-                self.creature_locations.insert(location, CreatureID(cid_ref.0));
-              }
-            });
-          }
+      MonsterAction::Throw(item, target) => {
+        if self.creatures.get(creature_id).unwrap().inventory.contains(&item) && self.item_registry.def(item).needs_target {
+          self.throw_item(creature_id, item, target);
         }
+        USE_ITEM_COST
       }
-      Some(other) => panic!("Item was not an item that can target: {}", other),
-      None => panic!("No such item letter: {}", item_letter),
+      MonsterAction::Wait => WAIT_COST,
     }
-    let item_used = item_to_use.unwrap();
-    let player_mut = self.creature_list.iter_mut().find(|creature_ref| creature_ref.is_the_player).unwrap();
-    for i in 0..player_mut.inventory.len() {
-      if player_mut.inventory[i] == item_used {
-        player_mut.inventory.remove(i);
-        break;
-      }
+  }
+
+  /// A monster attacks someone, up close or from afar. Monsters only ever
+  /// hurt the player.
+  fn monster_attack(&mut self, creature_id: CreatureID, target_id: CreatureID, from_afar: bool) {
+    if self.creatures.player_id() == Some(target_id) {
+      self.perform_attack(creature_id, target_id, from_afar);
+      let killer_name = self.creatures.get(creature_id).unwrap().name.clone();
+      self.check_player_death(|| DeathCause::Monster(killer_name));
     }
-    self.run_world_turn();
   }
 
-  pub fn run_world_turn(&mut self) {
-    let initiative_list: Vec<CreatureID> = self
-      .creature_list
+  /// Clears any dead NPCs out of the world.
+  fn remove_the_dead(&mut self) {
+    let dead_list: Vec<CreatureID> = self
+      .creatures
       .iter()
-      .filter_map(|creature_mut| {
-        if creature_mut.is_the_player || creature_mut.hit_points < 1 {
-          None
-        } else {
-          Some(CreatureID(creature_mut.id.0))
-        }
-      })
+      .filter(|&(creature_ref, _loc)| creature_ref.hit_points < 1 && !creature_ref.is_the_player)
+      .map(|(creature_ref, _loc)| creature_ref.id)
       .collect();
-    for creature_id_ref in initiative_list.iter() {
-      let my_location: Option<Location> = {
-        self
-          .creature_locations
-          .iter()
-          .find(|&(_loc, id)| id == creature_id_ref)
-          .map(|(&loc, _id)| loc)
-      };
-      match my_location {
-        None => println!("Creature {:?} is not anywhere!", creature_id_ref),
-        Some(loc) => {
-          // Look around
-          let seen_locations = {
-            let terrain_ref = &self.terrain;
-            let mut seen_locations = HashSet::new();
-            ppfov(
-              (loc.x, loc.y),
-              7,
-              |x, y| {
-                let here = *terrain_ref.get(&Location { x, y }).unwrap_or(&Terrain::Wall);
-                here == Terrain::Wall || here == Terrain::Ice
-              },
-              |x, y| {
-                seen_locations.insert(Location { x, y });
-              },
-            );
-            seen_locations
-          };
-          // Decide where to go
-          let move_target = if seen_locations.contains(&self.player_location) {
-            let terrain_ref = &self.terrain;
-            let path = a_star(self.player_location, loc, |loc| {
-              terrain_ref.get(&loc).unwrap_or(&Terrain::Wall) != &Terrain::Wall
-            }).expect("couldn't find a path");
-            debug_assert_eq!(loc, path[0]);
-            path[1]
-          } else {
-            loc + match self.gen.next_u32() >> 30 {
-              0 => Location { x: 0, y: 1 },
-              1 => Location { x: 0, y: -1 },
-              2 => Location { x: 1, y: 0 },
-              3 => Location { x: -1, y: 0 },
-              impossible => unreachable!("u32 >> 30: {}", impossible),
-            }
-          };
-          // go there
-          match self.creature_locations.get(&move_target) {
-            Some(target_id_ref) => {
-              // someone is there, do the attack!
-              let creature_damage_roll = {
-                let creature_ref = self
-                  .creature_list
-                  .iter()
-                  .find(|creature_ref| &creature_ref.id == creature_id_ref)
-                  .unwrap();
-                step(&mut self.gen, creature_ref.damage_step)
-              };
-              let target_ref_mut = self
-                .creature_list
-                .iter_mut()
-                .find(|creature_mut_ref| &creature_mut_ref.id == target_id_ref)
-                .unwrap();
-              if target_ref_mut.is_the_player {
-                target_ref_mut.hit_points -= creature_damage_roll;
-                println!("{:?} did {} damage to {:?}", creature_id_ref, creature_damage_roll, target_id_ref);
-              }
-              // TODO: log that we did damage.
-            }
-            None => match *self.terrain.entry(move_target).or_insert(Terrain::Floor) {
-              Terrain::Wall | Terrain::Ice => {
-                continue;
-              }
-              Terrain::Floor => {
-                let id = self.creature_locations.remove(&loc).expect("The creature wasn't where they should be!");
-                let old_id = self.creature_locations.insert(move_target, id);
-                debug_assert!(old_id.is_none());
-              }
-            },
-          }
+    for dead_id in dead_list.into_iter() {
+      if let Some((dead_creature, loc)) = self.creatures.remove(dead_id) {
+        // only the player's own kills get counted.
+        if dead_creature.hurt_by_player {
+          *self.kills.entry(dead_creature.name.clone()).or_insert(0) += 1;
         }
+        self.messages.push(GameEvent::Killed {
+          victim: Who::of(&dead_creature),
+          by_player: dead_creature.hurt_by_player,
+        });
+        self.drop_everything(dead_creature, loc);
       }
     }
-    // End Phase, we clear any dead NPCs off the list.
-    let creature_locations_mut = &mut self.creature_locations;
-    self.creature_list.retain(|creature_ref| {
-      let keep = creature_ref.hit_points > 0 || creature_ref.is_the_player;
-      if !keep {
-        let dead_location = *creature_locations_mut
-          .iter()
-          .find(|&(_, v_cid)| v_cid == &creature_ref.id)
-          .expect("Locations list out of sync!")
-          .0;
-        creature_locations_mut.remove(&dead_location);
-      };
-      keep
-    });
   }
 }
 
@@ -667,3 +717,58 @@ pub enum UseItemResult {
   ItemNeedsTarget,
   NoSuchItem,
 }
+
+#[test]
+fn test_worlds_are_independent() {
+  // two worlds in the same process must not share anything
+  let first = GameWorld::new(2018);
+  let second = GameWorld::new(2018);
+  assert_eq!(first.state_hash(), second.state_hash());
+}
+
+#[test]
+fn test_diagonal_moves_and_resting() {
+  let mut world = GameWorld::new(808);
+  let player_id = world.creatures.player_id().unwrap();
+  for cid in world.creatures.ids().into_iter().filter(|&cid| cid != player_id) {
+    world.creatures.remove(cid);
+  }
+  let start = world.player_location;
+  let up_right = Location { x: 1, y: 1 };
+  world.terrain.set(start + up_right, Terrain::Floor);
+  world.move_player(up_right);
+  assert_eq!(world.player_location, start + up_right);
+  assert_eq!(world.turn_count(), 1);
+
+  world.rest();
+  assert_eq!(world.player_location, start + up_right);
+  assert_eq!(world.turn_count(), 2);
+}
+
+#[test]
+fn test_only_the_players_kills_count() {
+  let mut world = GameWorld::test_arena(1111);
+  let player_id = world.creatures.player_id().unwrap();
+  let bomb = world.item_registry.by_name("Blast Bomb").unwrap();
+  let thrower_id = world.spawn_named_at("kestrel", world.player_location + Location { x: -3, y: 0 });
+  let victim_spot = world.player_location + Location { x: 3, y: 0 };
+
+  // a monster caught in another monster's blast doesn't count for the player
+  let victim_id = world.spawn_named_at("kestrel", victim_spot);
+  world.creatures.get_mut(victim_id).unwrap().hit_points = 1;
+  world.apply_item_effects(bomb, victim_spot, thrower_id);
+  world.remove_the_dead();
+  assert!(world.creatures.get(victim_id).is_none());
+  assert_eq!(world.total_kills(), 0);
+
+  let victim_id = world.spawn_named_at("kestrel", victim_spot);
+  world.creatures.get_mut(victim_id).unwrap().hit_points = 1;
+  world.messages.clear();
+  world.apply_item_effects(bomb, victim_spot, player_id);
+  world.remove_the_dead();
+  assert_eq!(world.total_kills(), 1);
+  assert!(world.messages.iter().any(|event| match event {
+    GameEvent::Killed { by_player, .. } => *by_player,
+    _ => false,
+  }));
+}
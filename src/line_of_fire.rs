@@ -0,0 +1,147 @@
+//! The paths that thrown and fired things take.
+//!
+//! Anything that flies goes along a Bresenham line from whoever let it go
+//! toward the spot they aimed at. It stops at the first creature in its way,
+//! or just short of the first wall (or ice) in its way, so it doesn't always
+//! end up where it was aimed.
+
+use super::*;
+
+/// Every location on the line from `from` to `to`, in order. `from` itself
+/// isn't included, but `to` is.
+pub fn bresenham_line(from: Location, to: Location) -> Vec<Location> {
+  let (dx, dy) = ((to.x - from.x).abs(), -(to.y - from.y).abs());
+  let (step_x, step_y) = ((to.x - from.x).signum(), (to.y - from.y).signum());
+  let mut error = dx + dy;
+  let mut here = from;
+  let mut out = Vec::with_capacity(dx.max(-dy) as usize);
+  while here != to {
+    let doubled = 2 * error;
+    if doubled >= dy {
+      error += dy;
+      here.x += step_x;
+    }
+    if doubled <= dx {
+      error += dx;
+      here.y += step_y;
+    }
+    out.push(here);
+  }
+  out
+}
+
+/// Where something that was thrown or fired went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trajectory {
+  /// Every tile that it went through, ending where it stopped.
+  pub path: Vec<Location>,
+  /// Where it stopped. If there's a wall right in front of the thrower,
+  /// that's the thrower's own spot.
+  pub impact: Location,
+  /// Who it hit, if a creature is what stopped it.
+  pub struck: Option<CreatureID>,
+}
+
+impl GameWorld {
+  /// Works out where something let go at `from` and aimed at `to` ends up.
+  /// Whoever is at `from` doesn't get in its way.
+  pub fn trace_projectile(&self, from: Location, to: Location) -> Trajectory {
+    let mut path = vec![];
+    for location in bresenham_line(from, to) {
+      if self.terrain.blocks_sight(location) {
+        break;
+      }
+      path.push(location);
+      if let Some(creature_id) = self.creatures.id_at(location) {
+        return Trajectory {
+          path,
+          impact: location,
+          struck: Some(creature_id),
+        };
+      }
+    }
+    Trajectory {
+      impact: path.last().cloned().unwrap_or(from),
+      path,
+      struck: None,
+    }
+  }
+
+  /// Has a creature throw an item that it's carrying at a spot. The item
+  /// goes off wherever it comes down.
+  pub(crate) fn throw_item(&mut self, thrower_id: CreatureID, item: Item, target: Location) {
+    let from = self.creatures.location_of(thrower_id).unwrap();
+    let impact = self.trace_projectile(from, target).impact;
+    let thrower_mut = self.creatures.get_mut(thrower_id).unwrap();
+    if let Some(i) = thrower_mut.inventory.iter().position(|&carried| carried == item) {
+      thrower_mut.inventory.remove(i);
+    }
+    self.messages.push(GameEvent::Threw {
+      who: Who::of(thrower_mut),
+      item_name: self.item_registry.def(item).name.clone(),
+    });
+    self.apply_item_effects(item, impact, thrower_id);
+  }
+}
+
+#[test]
+fn test_bresenham_line() {
+  let origin = Location { x: 0, y: 0 };
+  assert!(bresenham_line(origin, origin).is_empty());
+  let line = bresenham_line(origin, Location { x: 5, y: -2 });
+  assert_eq!(line.len(), 5);
+  assert_eq!(line.last(), Some(&Location { x: 5, y: -2 }));
+  // every step is to a neighbor, and it never doubles back
+  let mut previous = origin;
+  for &location in line.iter() {
+    assert_eq!(steps_between(previous, location), 1);
+    assert!(location.y <= previous.y);
+    previous = location;
+  }
+}
+
+#[test]
+fn test_projectiles_stop_at_walls_and_creatures() {
+  let mut world = GameWorld::test_arena(2024);
+  let from = world.player_location;
+  for x in 1..=6 {
+    let spot = from + Location { x, y: 0 };
+    world.terrain.set(spot, Terrain::Floor);
+    world.item_locations.remove(&spot);
+  }
+  let aim = from + Location { x: 6, y: 0 };
+  let clear = world.trace_projectile(from, aim);
+  assert_eq!((clear.impact, clear.struck, clear.path.len()), (aim, None, 6));
+
+  world.terrain.set(from + Location { x: 4, y: 0 }, Terrain::Wall);
+  assert_eq!(world.trace_projectile(from, aim).impact, from + Location { x: 3, y: 0 });
+
+  let blocker_spot = from + Location { x: 2, y: 0 };
+  let blocker_id = world.spawn_named_at("kestrel", blocker_spot);
+  let blocked = world.trace_projectile(from, aim);
+  assert_eq!((blocked.impact, blocked.struck), (blocker_spot, Some(blocker_id)));
+  assert_eq!(blocked.path, vec![from + Location { x: 1, y: 0 }, blocker_spot]);
+}
+
+#[test]
+fn test_no_throwing_into_a_wall() {
+  let mut world = GameWorld::test_arena(2525);
+  let bomb = world.item_registry.by_name("Blast Bomb").unwrap();
+  world.creatures.player_mut().unwrap().inventory = vec![bomb];
+  let wall = world.player_location + Location { x: 1, y: 0 };
+  world.terrain.set(wall, Terrain::Wall);
+  let (turn, hit_points) = (world.turn_count(), world.creatures.player().unwrap().hit_points);
+
+  assert!(!world.use_targeted_item('a', Location { x: 3, y: 0 }));
+  let player = world.creatures.player().unwrap();
+  assert_eq!(
+    (world.turn_count(), player.hit_points, player.inventory.clone()),
+    (turn, hit_points, vec![bomb])
+  );
+  assert_eq!(
+    world.messages.recent(0, 1).next(),
+    Some(&GameEvent::NoRoomToThrow {
+      item_name: "Blast Bomb".to_string()
+    })
+  );
+}
@@ -0,0 +1,143 @@
+//! Looking around the map without doing anything.
+//!
+//! A frontend asks for a `LocationDescription` of whatever spot the player is
+//! looking at, and shows its `lines` however it likes.
+
+use super::*;
+
+/// What a kind of terrain is called.
+pub fn terrain_name(terrain: Terrain) -> &'static str {
+  match terrain {
+    Terrain::Wall => "a wall",
+    Terrain::Floor => "the floor",
+    Terrain::Ice => "a sheet of ice",
+    Terrain::StairsDown => "stairs going down",
+    Terrain::StairsUp => "stairs going up",
+  }
+}
+
+/// A creature that the player is looking at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatureSighting {
+  pub who: Who,
+  pub hit_points: i32,
+  pub max_hit_points: i32,
+  pub statuses: Vec<StatusEffect>,
+}
+
+/// Everything that the player knows about one spot on the map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationDescription {
+  /// If the player can see it right now, rather than just remembering it.
+  /// Creatures are only ever described for spots in view.
+  pub in_view: bool,
+  pub terrain: Terrain,
+  pub creature: Option<CreatureSighting>,
+  /// The names of the items there, grouped up with how many of each. For a
+  /// remembered spot this is only the item the player remembers seeing.
+  pub items: Vec<(String, usize)>,
+}
+
+impl LocationDescription {
+  /// The description as lines of text, for a panel or a message.
+  pub fn lines(&self) -> Vec<String> {
+    let mut out = vec![];
+    out.push(format!(
+      "{} {}.",
+      if self.in_view { "You see" } else { "You remember" },
+      terrain_name(self.terrain)
+    ));
+    if let Some(ref sighting) = self.creature {
+      let name = if sighting.who.is_the_player {
+        "You".to_string()
+      } else {
+        with_article(&sighting.who.name)
+      };
+      out.push(format!("{} ({}/{} HP)", name, sighting.hit_points, sighting.max_hit_points));
+      for effect in sighting.statuses.iter() {
+        out.push(format!("  {} ({})", effect.kind, effect.turns_left));
+      }
+    }
+    for &(ref name, count) in self.items.iter() {
+      match count {
+        1 => out.push(with_article(name)),
+        count => out.push(format!("{} ({})", name, count)),
+      }
+    }
+    out
+  }
+}
+
+impl GameWorld {
+  /// If the player can see a spot or remembers it, so it's somewhere that
+  /// they can look at.
+  pub fn can_look_at(&self, location: Location) -> bool {
+    (self.visible.contains(&location) && self.terrain.in_bounds(location)) || self.map_memory.get(location).is_some()
+  }
+
+  /// What the player knows about a spot, or `None` if they've never seen it.
+  pub fn describe_location(&self, location: Location) -> Option<LocationDescription> {
+    if self.visible.contains(&location) && self.terrain.in_bounds(location) {
+      let creature = self.creatures.at(location).map(|creature| CreatureSighting {
+        who: Who::of(creature),
+        hit_points: creature.hit_points,
+        max_hit_points: creature.max_hit_points,
+        statuses: creature.statuses.clone(),
+      });
+      let mut counts = BTreeMap::new();
+      for &item in self.item_locations.get(&location).map(|items| items.as_slice()).unwrap_or(&[]) {
+        *counts.entry(item).or_insert(0) += 1;
+      }
+      Some(LocationDescription {
+        in_view: true,
+        terrain: self.terrain.get(location),
+        creature,
+        items: counts
+          .into_iter()
+          .map(|(item, count)| (self.item_registry.def(item).name.clone(), count))
+          .collect(),
+      })
+    } else {
+      self.map_memory.get(location).map(|remembered| LocationDescription {
+        in_view: false,
+        terrain: remembered.terrain,
+        creature: None,
+        items: remembered
+          .item
+          .map(|item| (self.item_registry.def(item).name.clone(), 1))
+          .into_iter()
+          .collect(),
+      })
+    }
+  }
+}
+
+#[test]
+fn test_describe_location() {
+  let mut world = GameWorld::new(6060);
+  let spot = world.player_location + Location { x: 1, y: 0 };
+  world.terrain.set(spot, Terrain::Floor);
+  if let Some(cid) = world.creatures.id_at(spot) {
+    world.creatures.remove(cid);
+  }
+  let def = world.monster_registry.by_name("kestrel").unwrap().clone();
+  let monster_id = world.creatures.spawn(spot, |id| Creature::from_def(id, &def)).unwrap();
+  world.creatures.get_mut(monster_id).unwrap().hit_points = 5;
+  let bomb = world.item_registry.by_name("Blast Bomb").unwrap();
+  world.item_locations.insert(spot, vec![bomb, bomb]);
+  world.mark_fov_dirty();
+  world.update_fov();
+
+  let seen = world.describe_location(spot).unwrap();
+  assert!(seen.in_view);
+  assert_eq!(seen.lines(), vec!["You see the floor.", "a kestrel (5/8 HP)", "Blast Bomb (2)"]);
+  let under_the_player = world.describe_location(world.player_location).unwrap();
+  assert_eq!(under_the_player.lines()[1], "You (20/20 HP)");
+
+  // once it's out of sight, only what was remembered is left
+  world.visible.clear();
+  let remembered = world.describe_location(spot).unwrap();
+  assert!(!remembered.in_view && remembered.creature.is_none());
+  assert_eq!(remembered.lines(), vec!["You remember the floor.", "a Blast Bomb"]);
+  assert!(world.describe_location(Location { x: -5, y: -5 }).is_none());
+}
@@ -0,0 +1,115 @@
+//! What the player can see right now, and what they remember of the places
+//! that they've seen before.
+//!
+//! Working out the field of view isn't cheap, so the world keeps the last one
+//! around and only works it out again once it's been marked dirty (the player
+//! moved, or the terrain changed).
+
+use super::*;
+
+/// How the player last saw a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RememberedTile {
+  pub terrain: Terrain,
+  /// The top item of the pile that was there, if any.
+  pub item: Option<Item>,
+}
+
+/// Every tile of a level that the player has seen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapMemory {
+  pub tiles: HashMap<Location, RememberedTile>,
+}
+
+impl MapMemory {
+  pub fn get(&self, loc: Location) -> Option<RememberedTile> {
+    self.tiles.get(&loc).cloned()
+  }
+
+  pub fn len(&self) -> usize {
+    self.tiles.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tiles.is_empty()
+  }
+}
+
+/// How far the player sees until a frontend says how big its screen is. It's
+/// enough to fill a screen 66 cells wide.
+pub const DEFAULT_FOV_RANGE: i32 = 33;
+
+impl GameWorld {
+  /// Changes how far the player sees, and works out what's in view again.
+  pub fn set_fov_range(&mut self, range: i32) {
+    self.fov_range = range;
+    self.mark_fov_dirty();
+    self.update_fov();
+  }
+
+  /// Makes the next `update_fov` work out the field of view again.
+  pub(crate) fn mark_fov_dirty(&mut self) {
+    self.fov_dirty = true;
+  }
+
+  /// Brings the player's field of view up to date if it's dirty, then has the
+  /// player remember how everything in view looks right now.
+  pub fn update_fov(&mut self) {
+    if self.fov_dirty {
+      let visible = &mut self.visible;
+      let terrain_ref = &self.terrain;
+      visible.clear();
+      ppfov(
+        (self.player_location.x, self.player_location.y),
+        self.fov_range,
+        |x, y| terrain_ref.blocks_sight(Location { x, y }),
+        |x, y| {
+          visible.insert(Location { x, y });
+        },
+      );
+      self.fov_dirty = false;
+    }
+    // Items can come and go without the view changing, so this part happens
+    // every time.
+    let terrain_ref = &self.terrain;
+    let item_locations_ref = &self.item_locations;
+    let tiles_mut = &mut self.map_memory.tiles;
+    for &loc in self.visible.iter().filter(|&&loc| terrain_ref.in_bounds(loc)) {
+      let tile = RememberedTile {
+        terrain: terrain_ref.get(loc),
+        item: item_locations_ref.get(&loc).and_then(|items| items.get(0)).cloned(),
+      };
+      tiles_mut.insert(loc, tile);
+    }
+  }
+}
+
+#[test]
+fn test_the_player_remembers_what_they_saw() {
+  let mut world = GameWorld::new(2468);
+  let player_id = world.creatures.player_id().unwrap();
+  for cid in world.creatures.ids().into_iter().filter(|&cid| cid != player_id) {
+    world.creatures.remove(cid);
+  }
+  let start = world.player_location;
+  assert!(world.visible.contains(&start));
+  assert_eq!(world.map_memory.get(start).map(|tile| tile.terrain), Some(world.terrain.get(start)));
+  let first_view: HashSet<Location> = world.visible.iter().cloned().filter(|&loc| world.terrain.in_bounds(loc)).collect();
+
+  // walk until something that was in view goes out of view
+  let mut gen = PCG32::new(5);
+  while first_view.iter().all(|loc| world.visible.contains(loc)) {
+    let delta = EIGHT_WAY_DELTAS[(gen.next_u32() >> 29) as usize];
+    world.move_player(delta);
+  }
+  let out_of_view = *first_view.iter().find(|loc| !world.visible.contains(loc)).unwrap();
+  assert!(world.map_memory.get(out_of_view).is_some());
+
+  // the memory is what was seen, not what's there now
+  let remembered = world.map_memory.get(out_of_view).unwrap();
+  world.terrain.set(out_of_view, Terrain::Ice);
+  world.mark_fov_dirty();
+  world.update_fov();
+  assert!(!world.visible.contains(&out_of_view));
+  assert_eq!(world.map_memory.get(out_of_view), Some(remembered));
+}
@@ -0,0 +1,343 @@
+//! The log of things that happened, for showing to the player.
+//!
+//! Events are kept as data rather than text so that tests can check exactly
+//! what happened. They aren't part of the save file (or the state hash), so a
+//! loaded game starts with an empty log.
+
+use super::*;
+
+/// The most events that a `MessageLog` holds before it drops the oldest ones.
+pub const MESSAGE_LOG_LIMIT: usize = 500;
+
+/// A creature, as named in an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Who {
+  pub id: CreatureID,
+  pub name: String,
+  pub is_the_player: bool,
+}
+
+impl Who {
+  pub fn of(creature: &Creature) -> Self {
+    Who {
+      id: creature.id,
+      name: creature.name.clone(),
+      is_the_player: creature.is_the_player,
+    }
+  }
+
+  /// How to write this creature at the start of a sentence.
+  fn subject(&self) -> String {
+    if self.is_the_player {
+      "You".to_string()
+    } else {
+      format!("The {}", self.name)
+    }
+  }
+
+  /// How to write this creature anywhere else in a sentence.
+  fn object(&self) -> String {
+    if self.is_the_player {
+      "you".to_string()
+    } else {
+      format!("the {}", self.name)
+    }
+  }
+
+  /// Picks the verb form that goes with this creature.
+  fn verb<'a>(&self, you_form: &'a str, it_form: &'a str) -> &'a str {
+    if self.is_the_player {
+      you_form
+    } else {
+      it_form
+    }
+  }
+}
+
+/// Something that happened in the world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+  Attack {
+    attacker: Who,
+    target: Who,
+    result: AttackResult,
+  },
+  /// An attack from a few tiles away.
+  Shot {
+    attacker: Who,
+    target: Who,
+    result: AttackResult,
+  },
+  /// A blast from an item hurt someone.
+  Blasted {
+    target: Who,
+    item_name: String,
+    damage: i32,
+  },
+  Killed {
+    victim: Who,
+    /// If the player is the one who killed it.
+    by_player: bool,
+  },
+  PickedUp {
+    who: Who,
+    item_name: String,
+  },
+  ItemUsed {
+    who: Who,
+    item_name: String,
+  },
+  Threw {
+    who: Who,
+    item_name: String,
+  },
+  /// The player tried to throw something, but it would've just hit them.
+  NoRoomToThrow {
+    item_name: String,
+  },
+  Equipped {
+    who: Who,
+    item_name: String,
+  },
+  Unequipped {
+    who: Who,
+    item_name: String,
+  },
+  StatusStarted {
+    who: Who,
+    status: StatusKind,
+  },
+  StatusEnded {
+    who: Who,
+    status: StatusKind,
+  },
+  PoisonDamage {
+    who: Who,
+    damage: i32,
+  },
+  /// The player heard a noise from somewhere they can't see.
+  Heard {
+    noise: NoiseKind,
+    /// Where the noise came from, relative to the player.
+    offset: Location,
+  },
+  /// The player couldn't take the stairs, since there was no room to stand
+  /// at the other end.
+  StairsBlocked,
+  /// Some tiles all changed from one terrain to another at once.
+  TerrainChanged {
+    locations: Vec<Location>,
+    from: Terrain,
+    to: Terrain,
+  },
+}
+
+/// Puts "a" or "an" in front of a name.
+pub(crate) fn with_article(name: &str) -> String {
+  match name.chars().next() {
+    Some(ch) if "AEIOUaeiou".contains(ch) => format!("an {}", name),
+    _ => format!("a {}", name),
+  }
+}
+
+/// Describes an attack, with `extra` (like " from afar") said after the
+/// target.
+fn write_attack(f: &mut ::std::fmt::Formatter, attacker: &Who, target: &Who, result: &AttackResult, extra: &str) -> ::std::fmt::Result {
+  let (subject, object) = (attacker.subject(), target.object());
+  if !result.hit {
+    write!(f, "{} {} {}{}.", subject, attacker.verb("miss", "misses"), object, extra)
+  } else if result.critical {
+    write!(f, "{} critically {} {}{} for {}!", subject, attacker.verb("hit", "hits"), object, extra, result.damage)
+  } else if result.damage == 0 {
+    write!(f, "{} {} {}{}, but the blow glances off.", subject, attacker.verb("hit", "hits"), object, extra)
+  } else {
+    write!(f, "{} {} {}{} for {}.", subject, attacker.verb("hit", "hits"), object, extra, result.damage)
+  }
+}
+
+impl ::std::fmt::Display for GameEvent {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    match self {
+      GameEvent::Attack { attacker, target, result } => write_attack(f, attacker, target, result, ""),
+      GameEvent::Shot { attacker, target, result } => write_attack(f, attacker, target, result, " from afar"),
+      GameEvent::Blasted { target, item_name, damage } => write!(
+        f,
+        "{} {} caught by the {} for {}.",
+        target.subject(),
+        target.verb("are", "is"),
+        item_name,
+        damage
+      ),
+      GameEvent::Killed { victim, by_player: true } if !victim.is_the_player => write!(f, "You kill {}!", victim.object()),
+      GameEvent::Killed { victim, .. } => write!(f, "{} {}!", victim.subject(), victim.verb("die", "dies")),
+      GameEvent::PickedUp { who, item_name } => write!(f, "{} {} up {}.", who.subject(), who.verb("pick", "picks"), with_article(item_name)),
+      GameEvent::ItemUsed { who, item_name } => write!(f, "{} {} {}.", who.subject(), who.verb("use", "uses"), with_article(item_name)),
+      GameEvent::Threw { who, item_name } => write!(f, "{} {} {}.", who.subject(), who.verb("throw", "throws"), with_article(item_name)),
+      GameEvent::StairsBlocked => write!(f, "There's no room at the other end of the stairs."),
+      GameEvent::NoRoomToThrow { item_name } => write!(f, "There's no room to throw the {}.", item_name),
+      GameEvent::Equipped { who, item_name } => write!(f, "{} {} {}.", who.subject(), who.verb("equip", "equips"), with_article(item_name)),
+      GameEvent::Unequipped { who, item_name } => {
+        write!(f, "{} {} off {}.", who.subject(), who.verb("take", "takes"), with_article(item_name))
+      }
+      GameEvent::StatusStarted { who, status } => match status {
+        StatusKind::Frozen => write!(f, "{} {} frozen solid!", who.subject(), who.verb("are", "is")),
+        StatusKind::Poisoned => write!(f, "{} {} poisoned!", who.subject(), who.verb("are", "is")),
+        StatusKind::Strengthened => write!(f, "{} {} stronger!", who.subject(), who.verb("feel", "looks")),
+        StatusKind::Hasted => write!(f, "{} {} up!", who.subject(), who.verb("speed", "speeds")),
+      },
+      GameEvent::StatusEnded { who, status } => match status {
+        StatusKind::Frozen => write!(f, "{} {} out.", who.subject(), who.verb("thaw", "thaws")),
+        StatusKind::Poisoned => write!(f, "{} {} better.", who.subject(), who.verb("feel", "looks")),
+        StatusKind::Strengthened => write!(f, "{} {} weaker.", who.subject(), who.verb("feel", "looks")),
+        StatusKind::Hasted => write!(f, "{} {} down.", who.subject(), who.verb("slow", "slows")),
+      },
+      GameEvent::PoisonDamage { who, damage } => write!(f, "{} {} {} from poison.", who.subject(), who.verb("take", "takes"), damage),
+      GameEvent::Heard { noise, offset } => match compass_direction(*offset) {
+        "nearby" => write!(f, "You hear {} nearby.", noise),
+        direction => write!(f, "You hear {} to {}.", noise, direction),
+      },
+      GameEvent::TerrainChanged { to, .. } => match to {
+        Terrain::Ice => write!(f, "The ground freezes over."),
+        Terrain::Floor => write!(f, "The ice melts away."),
+        Terrain::Wall | Terrain::StairsDown | Terrain::StairsUp => write!(f, "The ground shifts."),
+      },
+    }
+  }
+}
+
+/// Every recent `GameEvent`, oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageLog {
+  events: ::std::collections::VecDeque<GameEvent>,
+}
+
+impl MessageLog {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn push(&mut self, event: GameEvent) {
+    if self.events.len() >= MESSAGE_LOG_LIMIT {
+      self.events.pop_front();
+    }
+    self.events.push_back(event);
+  }
+
+  pub fn len(&self) -> usize {
+    self.events.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.events.is_empty()
+  }
+
+  /// Every event, oldest first.
+  pub fn iter(&self) -> impl Iterator<Item = &GameEvent> {
+    self.events.iter()
+  }
+
+  /// Up to `count` events, skipping the newest `skip` of them. They come out
+  /// newest first, which is how a scrollback panel draws them.
+  pub fn recent(&self, skip: usize, count: usize) -> impl Iterator<Item = &GameEvent> {
+    self.events.iter().rev().skip(skip).take(count)
+  }
+
+  pub fn clear(&mut self) {
+    self.events.clear();
+  }
+}
+
+#[test]
+fn test_message_text() {
+  let mut store = CreatureStore::new();
+  let player_id = store.spawn(Location { x: 0, y: 0 }, Creature::new_player).unwrap();
+  let monster_id = store
+    .spawn(Location { x: 1, y: 0 }, |id| {
+      let mut out = Creature::new(id, b'k', 0);
+      out.name = "kestrel".to_string();
+      out
+    })
+    .unwrap();
+  let player = Who::of(store.get(player_id).unwrap());
+  let monster = Who::of(store.get(monster_id).unwrap());
+
+  let hit = AttackResult {
+    attack_roll: 6,
+    defense: 3,
+    hit: true,
+    critical: false,
+    damage_roll: 3,
+    armor: 0,
+    damage: 3,
+  };
+  let mut log = MessageLog::new();
+  log.push(GameEvent::Attack {
+    attacker: player.clone(),
+    target: monster.clone(),
+    result: hit,
+  });
+  log.push(GameEvent::Killed {
+    victim: monster.clone(),
+    by_player: false,
+  });
+  log.push(GameEvent::Attack {
+    attacker: monster.clone(),
+    target: player.clone(),
+    result: AttackResult { damage: 2, ..hit },
+  });
+  let lines: Vec<String> = log.recent(0, 5).map(|event| event.to_string()).collect();
+  assert_eq!(
+    lines,
+    vec!["The kestrel hits you for 2.", "The kestrel dies!", "You hit the kestrel for 3."]
+  );
+  assert_eq!(log.recent(2, 5).count(), 1);
+  let player_kill = GameEvent::Killed {
+    victim: monster.clone(),
+    by_player: true,
+  };
+  assert_eq!(player_kill.to_string(), "You kill the kestrel!");
+
+  let miss = AttackResult {
+    hit: false,
+    damage_roll: 0,
+    damage: 0,
+    ..hit
+  };
+  let critical = AttackResult { critical: true, ..hit };
+  let glancing = AttackResult { armor: 5, damage: 0, ..hit };
+  let describe = |attacker: &Who, target: &Who, result: AttackResult, from_afar: bool| {
+    let (attacker, target) = (attacker.clone(), target.clone());
+    if from_afar {
+      GameEvent::Shot { attacker, target, result }.to_string()
+    } else {
+      GameEvent::Attack { attacker, target, result }.to_string()
+    }
+  };
+  assert_eq!(describe(&player, &monster, miss, false), "You miss the kestrel.");
+  assert_eq!(describe(&monster, &player, miss, true), "The kestrel misses you from afar.");
+  assert_eq!(describe(&player, &monster, critical, false), "You critically hit the kestrel for 3!");
+  assert_eq!(describe(&monster, &player, glancing, false), "The kestrel hits you, but the blow glances off.");
+}
+
+#[test]
+fn test_attacks_are_logged() {
+  let mut world = GameWorld::new(777);
+  let delta = Location { x: 1, y: 0 };
+  let target_spot = world.player_location + delta;
+  if let Some(cid) = world.creatures.id_at(target_spot) {
+    world.creatures.remove(cid);
+  }
+  world.terrain.set(target_spot, Terrain::Floor);
+  let def = world.monster_registry.by_name("kestrel").unwrap().clone();
+  let target_id = world.creatures.spawn(target_spot, |id| Creature::from_def(id, &def)).unwrap();
+  world.messages.clear();
+  world.move_player(delta);
+  let first_event = world.messages.iter().next().cloned();
+  match first_event {
+    Some(GameEvent::Attack { attacker, target, .. }) => {
+      assert!(attacker.is_the_player);
+      assert_eq!(target.id, target_id);
+    }
+    other => panic!("expected an attack, got {:?}", other),
+  }
+}
@@ -0,0 +1,168 @@
+//! The kinds of monster that can show up, loaded from `data/monsters.txt`.
+
+use super::*;
+
+/// The monster definitions that are built into the game.
+pub const MONSTER_DEFS_TEXT: &str = include_str!("../data/monsters.txt");
+
+/// How a monster decides what to do with its turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AiKind {
+  /// Goes after the player once it can see them.
+  Chaser,
+  /// Walks around at random, and only fights if it bumps into someone.
+  Wanderer,
+  /// A chaser that runs away once it's badly hurt.
+  Coward,
+  /// Keeps its distance and attacks from afar.
+  Kiter,
+  /// Gathers with others of its kind and only chases the player as a pack.
+  Pack,
+}
+
+impl ::std::str::FromStr for AiKind {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    match s {
+      "chaser" => Ok(AiKind::Chaser),
+      "wanderer" => Ok(AiKind::Wanderer),
+      "coward" => Ok(AiKind::Coward),
+      "kiter" => Ok(AiKind::Kiter),
+      "pack" => Ok(AiKind::Pack),
+      _ => Err(()),
+    }
+  }
+}
+
+/// Everything needed to make one kind of monster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonsterDef {
+  pub name: String,
+  pub glyph: u8,
+  pub color: u32,
+  pub hit_points: i32,
+  pub attack_step: i32,
+  pub damage_step: i32,
+  pub defense: i32,
+  pub armor: i32,
+  pub sight: i32,
+  pub speed: i32,
+  pub ai: AiKind,
+  /// How many turns it keeps hunting for the player after losing them.
+  pub memory_span: u32,
+  /// The names of the items that it starts out wearing.
+  pub equipment: Vec<String>,
+  /// The names of the items that it starts out carrying, such as things to
+  /// throw.
+  pub carries: Vec<String>,
+  /// The shallowest and deepest levels this monster spawns on (inclusive).
+  pub depths: (u32, u32),
+  pub rarity: u32,
+}
+
+/// Reads an optional list of item names, separated by commas.
+fn name_list(section: &DefSection, key: &str) -> Result<Vec<String>, DefError> {
+  if section.has(key) {
+    Ok(section.get_str(key)?.split(',').map(|name| name.trim().to_string()).collect())
+  } else {
+    Ok(vec![])
+  }
+}
+
+impl MonsterDef {
+  fn from_section(section: &DefSection) -> Result<Self, DefError> {
+    Ok(MonsterDef {
+      name: section.name.clone(),
+      glyph: section.get_glyph("glyph")?,
+      color: section.get_color("color")?,
+      hit_points: section.get("hp")?,
+      damage_step: section.get("damage_step")?,
+      attack_step: section.get_or("attack_step", 5)?,
+      defense: section.get_or("defense", 3)?,
+      armor: section.get_or("armor", 0)?,
+      sight: section.get("sight")?,
+      speed: section.get_or("speed", 10)?,
+      ai: section.get("ai")?,
+      memory_span: section.get_or("memory", DEFAULT_MEMORY_SPAN)?,
+      equipment: name_list(section, "equipment")?,
+      carries: name_list(section, "carries")?,
+      depths: section.get_range("depth")?,
+      rarity: section.get_rarity("rarity")?,
+    })
+  }
+
+  pub fn spawns_at(&self, depth: u32) -> bool {
+    self.depths.0 <= depth && depth <= self.depths.1
+  }
+}
+
+/// All of the monster kinds in the game.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MonsterRegistry {
+  pub defs: Vec<MonsterDef>,
+}
+
+impl MonsterRegistry {
+  pub fn parse(text: &str) -> Result<Self, DefError> {
+    let mut defs = vec![];
+    for section in parse_def_sections(text)? {
+      defs.push(MonsterDef::from_section(&section)?);
+    }
+    Ok(MonsterRegistry { defs })
+  }
+
+  /// The monsters from `data/monsters.txt`.
+  pub fn builtin() -> Self {
+    Self::parse(MONSTER_DEFS_TEXT).unwrap_or_else(|e| panic!("bad data/monsters.txt, {}", e))
+  }
+
+  pub fn by_name(&self, name: &str) -> Option<&MonsterDef> {
+    self.defs.iter().find(|def| def.name == name)
+  }
+
+  /// Picks a monster for the depth given, with the common ones more likely.
+  /// Gives `None` if nothing lives that deep.
+  pub fn pick_for_depth(&self, depth: u32, gen: &mut PCG32) -> Option<&MonsterDef> {
+    let choices = self.defs.iter().filter(|def| def.spawns_at(depth)).collect();
+    pick_by_rarity(choices, |def| def.rarity, gen)
+  }
+}
+
+impl Creature {
+  pub fn from_def(id: CreatureID, def: &MonsterDef) -> Self {
+    let mut out = Self::new(id, def.glyph, def.color);
+    out.name = def.name.clone();
+    out.hit_points = def.hit_points;
+    out.max_hit_points = def.hit_points;
+    out.damage_step = def.damage_step;
+    out.attack_step = def.attack_step;
+    out.defense = def.defense;
+    out.armor = def.armor;
+    out.sight = def.sight;
+    out.speed = def.speed;
+    out.ai = def.ai;
+    out.memory_span = def.memory_span;
+    out
+  }
+}
+
+#[test]
+fn test_builtin_monsters() {
+  let registry = MonsterRegistry::builtin();
+  assert_eq!(registry.by_name("kestrel").map(|def| def.glyph), Some(b'k'));
+  let mut gen = PCG32::new(5);
+  for depth in 1..=DEEPEST_LEVEL {
+    for _ in 0..20 {
+      assert!(registry.pick_for_depth(depth, &mut gen).unwrap().spawns_at(depth));
+    }
+  }
+  assert!(registry.pick_for_depth(DEEPEST_LEVEL + 1, &mut gen).is_none());
+  // every piece of equipment has to be a real item that can be worn
+  let items = ItemRegistry::builtin();
+  for name in registry.defs.iter().flat_map(|def| def.equipment.iter()) {
+    assert!(items.by_name(name).map(|item| items.def(item).slot.is_some()).unwrap_or(false), "{}", name);
+  }
+  for name in registry.defs.iter().flat_map(|def| def.carries.iter()) {
+    assert!(items.by_name(name).is_some(), "{}", name);
+  }
+}
@@ -0,0 +1,152 @@
+//! The end of the game, and the morgue file that remembers it.
+
+use super::*;
+
+use std::io::{self, Write};
+
+/// What killed the player.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeathCause {
+  /// Killed in a fight, by the kind of monster named.
+  Monster(String),
+  /// Caught in the blast of their own item, named here.
+  OwnItem(String),
+  /// Poisoned to death.
+  Poison,
+}
+
+impl ::std::fmt::Display for DeathCause {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    match self {
+      DeathCause::Monster(name) => write!(f, "Killed by {}", with_article(name)),
+      DeathCause::OwnItem(name) => write!(f, "Caught in the blast of their own {}", name),
+      DeathCause::Poison => write!(f, "Succumbed to poison"),
+    }
+  }
+}
+
+/// How the game ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameOver {
+  pub cause: DeathCause,
+  pub depth: u32,
+  pub turn: u64,
+}
+
+impl GameWorld {
+  /// If the game is over, how it ended.
+  pub fn game_over(&self) -> Option<&GameOver> {
+    self.game_over.as_ref()
+  }
+
+  /// Ends the game if the player is out of hit points. Only the first cause
+  /// of death counts.
+  pub(crate) fn check_player_death<F>(&mut self, cause: F)
+  where
+    F: FnOnce() -> DeathCause,
+  {
+    let player_dead = self.creatures.player().map(|player| player.hit_points < 1).unwrap_or(false);
+    if player_dead && self.game_over.is_none() {
+      self.game_over = Some(GameOver {
+        cause: cause(),
+        depth: self.depth,
+        turn: self.turn_count(),
+      });
+      let victim = Who::of(self.creatures.player().unwrap());
+      self.messages.push(GameEvent::Killed { victim, by_player: false });
+    }
+  }
+
+  /// The total number of monsters that the player has killed.
+  pub fn total_kills(&self) -> u32 {
+    self.kills.values().sum()
+  }
+
+  /// Writes out a plain text record of the game: how it went, what the player
+  /// had, and what the level looked like at the end.
+  pub fn write_morgue<W: Write>(&self, mut w: W) -> io::Result<()> {
+    writeln!(w, "Kasidin, seed {}", self.seed)?;
+    match self.game_over() {
+      Some(game_over) => writeln!(w, "{} on depth {}, turn {}.", game_over.cause, game_over.depth, game_over.turn)?,
+      None => writeln!(w, "Still alive on depth {}, turn {}.", self.depth, self.turn_count())?,
+    }
+    writeln!(w)?;
+
+    writeln!(w, "Kills: {}", self.total_kills())?;
+    for (name, count) in self.kills.iter() {
+      writeln!(w, "  {} {}", count, name)?;
+    }
+    writeln!(w)?;
+
+    writeln!(w, "Inventory:")?;
+    let mut inventory = BTreeMap::new();
+    for &item in self.creatures.player().map(|player| player.inventory.as_slice()).unwrap_or(&[]) {
+      *inventory.entry(item).or_insert(0) += 1;
+    }
+    if inventory.is_empty() {
+      writeln!(w, "  (nothing)")?;
+    }
+    for (item, count) in inventory.into_iter() {
+      writeln!(w, "  {} {}", count, self.item_registry.def(item).name)?;
+    }
+    writeln!(w)?;
+
+    writeln!(w, "Equipment:")?;
+    let equipment = self.creatures.player().map(|player| player.equipment.clone()).unwrap_or_default();
+    if equipment.is_empty() {
+      writeln!(w, "  (nothing)")?;
+    }
+    for (slot, item) in equipment.into_iter() {
+      writeln!(w, "  {}: {}", slot, self.item_registry.def(item).name)?;
+    }
+    writeln!(w)?;
+
+    writeln!(w, "Final map of depth {}:", self.depth)?;
+    for y in (0..self.terrain.height() as i32).rev() {
+      let mut line = String::new();
+      for x in 0..self.terrain.width() as i32 {
+        let loc = Location { x, y };
+        let glyph = match self.creatures.at(loc) {
+          Some(creature) => creature.icon,
+          None => match self.item_locations.get(&loc).and_then(|items| items.get(0)) {
+            Some(&item) => self.item_registry.def(item).glyph,
+            None => match self.terrain.get(loc) {
+              Terrain::Wall => WALL_TILE,
+              Terrain::Ice => b'~',
+              Terrain::Floor => b'.',
+              Terrain::StairsDown => b'>',
+              Terrain::StairsUp => b'<',
+            },
+          },
+        };
+        line.push(glyph_char(glyph));
+      }
+      writeln!(w, "{}", line.trim_end())?;
+    }
+    w.flush()
+  }
+}
+
+#[test]
+fn test_player_death() {
+  let mut world = GameWorld::new(4242);
+  assert!(world.game_over().is_none());
+  // pretend that the first monster we find got in a lucky hit
+  let monster_id = world.creatures.iter().find(|&(creature, _)| !creature.is_the_player).unwrap().0.id;
+  world.creatures.player_mut().unwrap().hit_points = 0;
+  let monster_name = world.creatures.get(monster_id).unwrap().name.clone();
+  world.check_player_death(|| DeathCause::Monster(monster_name.clone()));
+  assert_eq!(world.game_over().map(|game_over| &game_over.cause), Some(&DeathCause::Monster(monster_name)));
+
+  // a dead player can't do anything
+  let hash = world.state_hash();
+  world.move_player(Location { x: 1, y: 0 });
+  assert_eq!(hash, world.state_hash());
+
+  let mut morgue = vec![];
+  world.write_morgue(&mut morgue).unwrap();
+  let text = String::from_utf8(morgue).unwrap();
+  assert!(text.contains("seed 4242"));
+  assert!(text.contains("Killed by "));
+  assert!(text.contains('@'));
+}
@@ -0,0 +1,174 @@
+//! Noises, how far they carry, and who hears them.
+//!
+//! A noise starts out with some loudness at its source and gets quieter as it
+//! goes, by 1 for each open tile and by a lot more for each wall tile that it
+//! has to get through. Anywhere it's still above 0 it can be heard.
+
+use super::*;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// How loud a fight is.
+pub const COMBAT_LOUDNESS: i32 = 8;
+
+/// How loud it is when ice gets broken up.
+pub const ICE_SHATTER_LOUDNESS: i32 = 12;
+
+/// How much quieter a noise gets going through one wall tile.
+pub const WALL_MUFFLING: i32 = 5;
+
+/// What a noise sounds like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NoiseKind {
+  Combat,
+  Explosion,
+  IceShattering,
+}
+
+impl ::std::fmt::Display for NoiseKind {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    match self {
+      NoiseKind::Combat => write!(f, "fighting"),
+      NoiseKind::Explosion => write!(f, "an explosion"),
+      NoiseKind::IceShattering => write!(f, "ice shattering"),
+    }
+  }
+}
+
+/// Finds how loud a noise is everywhere it can be heard.
+pub fn spread_noise(terrain: &TerrainMap, source: Location, loudness: i32) -> HashMap<Location, i32> {
+  let mut heard = HashMap::new();
+  if loudness <= 0 {
+    return heard;
+  }
+  // This is Dijkstra's, with how much quieter the noise has gotten as the
+  // distance.
+  let mut open_set = BinaryHeap::new();
+  open_set.push(Reverse((0, source.x, source.y)));
+  while let Some(Reverse((fade, x, y))) = open_set.pop() {
+    let loc = Location { x, y };
+    if heard.contains_key(&loc) {
+      continue;
+    }
+    heard.insert(loc, loudness - fade);
+    for neighbor in loc.neighbors().filter(|&neighbor| terrain.in_bounds(neighbor)) {
+      let cost = match terrain.get(neighbor) {
+        Terrain::Wall => WALL_MUFFLING,
+        Terrain::Floor | Terrain::Ice | Terrain::StairsDown | Terrain::StairsUp => 1,
+      };
+      if fade + cost < loudness && !heard.contains_key(&neighbor) {
+        open_set.push(Reverse((fade + cost, neighbor.x, neighbor.y)));
+      }
+    }
+  }
+  heard
+}
+
+/// Describes which way something is, given how far off it is.
+pub fn compass_direction(offset: Location) -> &'static str {
+  let (ax, ay) = (offset.x.abs(), offset.y.abs());
+  let north_south = if ay * 2 < ax {
+    ""
+  } else if offset.y > 0 {
+    "north"
+  } else {
+    "south"
+  };
+  let east_west = if ax * 2 < ay {
+    ""
+  } else if offset.x > 0 {
+    "east"
+  } else {
+    "west"
+  };
+  match (north_south, east_west) {
+    ("", "") => "nearby",
+    ("north", "") => "the north",
+    ("south", "") => "the south",
+    ("", "east") => "the east",
+    ("", "west") => "the west",
+    ("north", "east") => "the northeast",
+    ("north", "west") => "the northwest",
+    ("south", "east") => "the southeast",
+    _ => "the southwest",
+  }
+}
+
+impl GameWorld {
+  /// If the player could see a location as of the last `update_fov`. This
+  /// doesn't work the field of view out again, even if it's dirty.
+  pub fn player_can_see(&self, target: Location) -> bool {
+    self.visible.contains(&target)
+  }
+
+  /// Makes a noise. Every monster that hears it goes to investigate, and if the
+  /// player hears it without seeing where it came from they get told about it.
+  pub(crate) fn make_noise(&mut self, source: Location, loudness: i32, kind: NoiseKind) {
+    let heard = spread_noise(&self.terrain, source, loudness);
+    if self.creatures.player().is_some() && heard.contains_key(&self.player_location) && !self.player_can_see(source) {
+      self.messages.push(GameEvent::Heard {
+        noise: kind,
+        offset: source - self.player_location,
+      });
+    }
+    let listeners: Vec<CreatureID> = self
+      .creatures
+      .iter()
+      .filter(|&(creature, loc)| !creature.is_the_player && heard.contains_key(&loc))
+      .map(|(creature, _loc)| creature.id)
+      .collect();
+    for cid in listeners.into_iter() {
+      self.creatures.get_mut(cid).unwrap().investigating = Some(source);
+    }
+  }
+}
+
+#[test]
+fn test_walls_muffle_noise() {
+  let mut terrain = TerrainMap::new(20, 3, Terrain::Floor);
+  let heard = spread_noise(&terrain, Location { x: 0, y: 1 }, 10);
+  assert_eq!(heard.get(&Location { x: 0, y: 1 }), Some(&10));
+  assert_eq!(heard.get(&Location { x: 4, y: 1 }), Some(&6));
+  assert_eq!(heard.get(&Location { x: 10, y: 1 }), None);
+
+  // a solid wall in the way costs a lot more than open floor
+  for y in 0..3 {
+    terrain.set(Location { x: 2, y }, Terrain::Wall);
+  }
+  let heard = spread_noise(&terrain, Location { x: 0, y: 1 }, 10);
+  assert_eq!(heard.get(&Location { x: 2, y: 1 }), Some(&(10 - 1 - WALL_MUFFLING)));
+  assert_eq!(heard.get(&Location { x: 4, y: 1 }), Some(&(10 - 3 - WALL_MUFFLING)));
+  assert_eq!(heard.get(&Location { x: 6, y: 1 }), None);
+  assert_eq!(compass_direction(Location { x: 5, y: 4 }), "the northeast");
+  assert_eq!(compass_direction(Location { x: -5, y: 1 }), "the west");
+}
+
+#[test]
+fn test_monsters_investigate_noises() {
+  let mut world = GameWorld::test_arena(202);
+  // find somewhere out of sight that a loud noise would still carry from.
+  let seen = world.visible.clone();
+  let heard_by_player = spread_noise(&world.terrain, world.player_location, 20);
+  let source = world
+    .terrain
+    .iter()
+    .find(|&(loc, terrain)| terrain == Terrain::Floor && !seen.contains(&loc) && heard_by_player.contains_key(&loc))
+    .unwrap()
+    .0;
+  let monster_id = world.spawn_named_at("kestrel", source);
+
+  world.messages.clear();
+  world.make_noise(source, 20, NoiseKind::Explosion);
+  let expected = GameEvent::Heard {
+    noise: NoiseKind::Explosion,
+    offset: source - world.player_location,
+  };
+  assert_eq!(world.messages.iter().collect::<Vec<_>>(), vec![&expected]);
+  // hearing something isn't the same as seeing the player there
+  let monster = world.creatures.get(monster_id).unwrap();
+  assert_eq!((monster.investigating, monster.memory), (Some(source), None));
+  // it's already where the noise was, so there's nothing left to look into
+  world.rest();
+  assert_eq!(world.creatures.get(monster_id).unwrap().investigating, None);
+}
@@ -26,9 +26,11 @@ where
   let mut f_score = HashMap::new();
   f_score.insert(start, heuristic_cost_estimate(start, goal));
   while !open_set.is_empty() {
+    // HashSet iteration order is random, so ties have to be broken by the
+    // location itself or the same search can give different paths.
     let current = *open_set
       .iter()
-      .min_by_key(|loc_ref| f_score[loc_ref])
+      .min_by_key(|loc_ref| (f_score[loc_ref], loc_ref.x, loc_ref.y))
       .expect("the open set should not have been empty because of the loop condition.");
     if current == goal {
       return Some(reconstruct_path(came_from, current));
@@ -50,3 +52,149 @@ where
   }
   None
 }
+
+/// Which ways a path can go from each tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Movement {
+  /// Only up, down, left, and right.
+  FourWay,
+  /// Diagonals too.
+  EightWay,
+}
+
+/// What a straight step costs in `find_path`, before the tile's own cost.
+pub const ORTHOGONAL_STEP: u32 = 10;
+
+/// What a diagonal step costs in `find_path`, before the tile's own cost.
+/// This is close enough to `ORTHOGONAL_STEP` times the square root of 2.
+pub const DIAGONAL_STEP: u32 = 14;
+
+/// How a `find_path` search should go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PathOptions {
+  pub movement: Movement,
+  /// The most tiles that the search will look at before it gives up.
+  pub budget: usize,
+}
+
+impl Default for PathOptions {
+  fn default() -> Self {
+    PathOptions {
+      movement: Movement::FourWay,
+      budget: ::std::usize::MAX,
+    }
+  }
+}
+
+/// The best possible cost between two spots, if every tile cost 1.
+fn octile_distance(a: Location, b: Location, movement: Movement) -> u32 {
+  let dx = (a.x - b.x).abs() as u32;
+  let dy = (a.y - b.y).abs() as u32;
+  match movement {
+    Movement::FourWay => ORTHOGONAL_STEP * (dx + dy),
+    Movement::EightWay => ORTHOGONAL_STEP * dx.max(dy) + (DIAGONAL_STEP - ORTHOGONAL_STEP) * dx.min(dy),
+  }
+}
+
+/// Gives the path from `start` to `goal` (in forward order, both ends
+/// included) with the lowest total cost, if there is one.
+///
+/// `tile_cost` gives how costly a tile is to step onto, or `None` if it can't
+/// be walked on at all. Every cost has to be at least 1. The search gives up
+/// with `None` if it would need to look at more tiles than the budget.
+pub fn find_path<C>(start: Location, goal: Location, options: PathOptions, tile_cost: C) -> Option<Path>
+where
+  C: Fn(Location) -> Option<u32>,
+{
+  use std::cmp::Reverse;
+  use std::collections::BinaryHeap;
+
+  let mut open_set = BinaryHeap::new();
+  let mut came_from = HashMap::new();
+  let mut g_score = HashMap::new();
+  let mut closed_set = HashSet::new();
+  g_score.insert(start, 0u32);
+  // Ties get broken by the location so that the same search always gives the
+  // same path.
+  open_set.push(Reverse((octile_distance(start, goal, options.movement), start.x, start.y)));
+  while let Some(Reverse((_f, x, y))) = open_set.pop() {
+    let current = Location { x, y };
+    if current == goal {
+      let mut path = reconstruct_path(came_from, current);
+      path.reverse();
+      return Some(path);
+    }
+    if !closed_set.insert(current) {
+      continue;
+    }
+    if closed_set.len() > options.budget {
+      return None;
+    }
+    let current_g = g_score[&current];
+    let neighbor_count = match options.movement {
+      Movement::FourWay => 4,
+      Movement::EightWay => 8,
+    };
+    for &delta in EIGHT_WAY_DELTAS[..neighbor_count].iter() {
+      let neighbor = current + delta;
+      if closed_set.contains(&neighbor) {
+        continue;
+      }
+      let cost = match tile_cost(neighbor) {
+        Some(cost) => cost,
+        None => continue,
+      };
+      let step = if delta.x != 0 && delta.y != 0 { DIAGONAL_STEP } else { ORTHOGONAL_STEP };
+      let tentative_g_score = current_g.saturating_add(step.saturating_mul(cost));
+      if tentative_g_score < *g_score.get(&neighbor).unwrap_or(&::std::u32::MAX) {
+        came_from.insert(neighbor, current);
+        g_score.insert(neighbor, tentative_g_score);
+        let f_score = tentative_g_score.saturating_add(octile_distance(neighbor, goal, options.movement));
+        open_set.push(Reverse((f_score, neighbor.x, neighbor.y)));
+      }
+    }
+  }
+  None
+}
+
+#[test]
+fn test_find_path() {
+  let mut terrain = TerrainMap::new(10, 10, Terrain::Floor);
+  for y in 0..8 {
+    terrain.set(Location { x: 5, y }, Terrain::Wall);
+  }
+  let walkable = |loc: Location| terrain.get(loc).is_walkable();
+  let unit_cost = |loc: Location| if terrain.get(loc).is_walkable() { Some(1) } else { None };
+  let start = Location { x: 1, y: 1 };
+  let goal = Location { x: 8, y: 1 };
+
+  // four way with every tile costing 1 is the same as the old search
+  let path = find_path(start, goal, PathOptions::default(), unit_cost).unwrap();
+  assert_eq!(path.len(), a_star(start, goal, walkable).unwrap().len());
+  assert_eq!((path[0], path[path.len() - 1]), (start, goal));
+  assert!(path.windows(2).all(|pair| {
+    let d = pair[1] - pair[0];
+    d.x.abs() + d.y.abs() == 1
+  }));
+
+  // diagonals cut the trip around the wall short
+  let eight_way = PathOptions {
+    movement: Movement::EightWay,
+    ..PathOptions::default()
+  };
+  assert!(find_path(start, goal, eight_way, unit_cost).unwrap().len() < path.len());
+
+  // costly tiles get walked around if that's cheaper
+  let swamp = Location { x: 5, y: 8 };
+  let swamp_cost = |loc: Location| if loc == swamp { Some(50) } else { unit_cost(loc) };
+  let swamp_path = find_path(start, goal, PathOptions::default(), swamp_cost).unwrap();
+  assert!(!swamp_path.contains(&swamp));
+  assert!(swamp_path.contains(&Location { x: 5, y: 9 }));
+
+  // and a small budget gives up before it gets there
+  let tight = PathOptions {
+    budget: 10,
+    ..PathOptions::default()
+  };
+  assert_eq!(find_path(start, goal, tight, unit_cost), None);
+}
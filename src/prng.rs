@@ -16,31 +16,52 @@ pub fn u64_from_time() -> u64 {
 }
 
 /// Rolls a step roll, according to the 4th edition chart.
-pub fn step(gen: &mut PCG32, mut step: i32) -> i32 {
+pub fn step(gen: &mut PCG32, step: i32) -> i32 {
+  step_roll(gen, step).total
+}
+
+/// A step roll, along with if any of its dice exploded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepRoll {
+  pub total: i32,
+  pub exploded: bool,
+}
+
+/// Rolls a step roll like `step` does, but also tells if it exploded.
+pub fn step_roll(gen: &mut PCG32, mut step: i32) -> StepRoll {
   if step < 1 {
-    0
-  } else {
-    let mut total = 0;
-    while step > 13 {
-      total += d12.explode(gen);
-      step -= 7;
-    }
-    (total + match step {
-      1 => (d4.explode(gen) as i32 - 2).max(1) as u32,
-      2 => (d4.explode(gen) as i32 - 1).max(1) as u32,
-      3 => d4.explode(gen),
-      4 => d6.explode(gen),
-      5 => d8.explode(gen),
-      6 => d10.explode(gen),
-      7 => d12.explode(gen),
-      8 => d6.explode(gen) + d6.explode(gen),
-      9 => d8.explode(gen) + d6.explode(gen),
-      10 => d8.explode(gen) + d8.explode(gen),
-      11 => d10.explode(gen) + d8.explode(gen),
-      12 => d10.explode(gen) + d10.explode(gen),
-      13 => d12.explode(gen) + d10.explode(gen),
-      _other => unreachable!(),
-    }) as i32
+    return StepRoll { total: 0, exploded: false };
+  }
+  let mut exploded = false;
+  let mut roll = |die: RandRangeInclusive32, gen: &mut PCG32| {
+    let out = die.explode(gen);
+    exploded |= out > die.high();
+    out
+  };
+  let mut total = 0;
+  while step > 13 {
+    total += roll(d12, gen);
+    step -= 7;
+  }
+  total += match step {
+    1 => (roll(d4, gen) as i32 - 2).max(1) as u32,
+    2 => (roll(d4, gen) as i32 - 1).max(1) as u32,
+    3 => roll(d4, gen),
+    4 => roll(d6, gen),
+    5 => roll(d8, gen),
+    6 => roll(d10, gen),
+    7 => roll(d12, gen),
+    8 => roll(d6, gen) + roll(d6, gen),
+    9 => roll(d8, gen) + roll(d6, gen),
+    10 => roll(d8, gen) + roll(d8, gen),
+    11 => roll(d10, gen) + roll(d8, gen),
+    12 => roll(d10, gen) + roll(d10, gen),
+    13 => roll(d12, gen) + roll(d10, gen),
+    _other => unreachable!(),
+  };
+  StepRoll {
+    total: total as i32,
+    exploded,
   }
 }
 
@@ -61,6 +82,12 @@ impl PCG32 {
     Self { state }
   }
 
+  /// The current internal state, which can be passed to `new` to make a
+  /// generator that continues the same sequence.
+  pub fn state(&self) -> u64 {
+    self.state
+  }
+
   pub fn next_u32(&mut self) -> u32 {
     const A: u64 = 6364136223846793005;
     const C: u64 = 1442695040888963407; // this can be any odd const
@@ -0,0 +1,407 @@
+//! Drawing the game into a grid of glyphs.
+//!
+//! Everything here draws into a `Screen`, which is just three plain images
+//! (foreground color, background color, and glyph). The windowed frontend
+//! copies a `Screen` into its `DwarfTerm`, and the headless frontend turns it
+//! into text, so both of them always show the exact same thing.
+//!
+//! Like the `DwarfTerm`, the origin is in the lower left, so `y` goes _up_ the
+//! screen.
+
+use super::*;
+
+pub const KINDA_LIME_GREEN: u32 = rgb32!(128, 255, 20);
+
+/// A grid of glyphs with colors, the same layout as a `DwarfTerm`.
+#[derive(Debug, Clone)]
+pub struct Screen {
+  pub fgs: VecImage<u32>,
+  pub bgs: VecImage<u32>,
+  pub ids: VecImage<u8>,
+}
+
+impl Screen {
+  pub fn new(width: usize, height: usize) -> Self {
+    let mut out = Screen {
+      fgs: VecImage::new(width, height),
+      bgs: VecImage::new(width, height),
+      ids: VecImage::new(width, height),
+    };
+    out.clear();
+    out
+  }
+
+  pub fn width(&self) -> usize {
+    self.ids.width()
+  }
+
+  pub fn height(&self) -> usize {
+    self.ids.height()
+  }
+
+  /// Blanks every cell to white-on-black.
+  pub fn clear(&mut self) {
+    self.fgs.set_all(rgb32!(255, 255, 255));
+    self.bgs.set_all(rgb32!(0, 0, 0));
+    self.ids.set_all(0);
+  }
+
+  /// Writes the bytes of the text starting at the position given. Anything
+  /// that would go past the right edge is cut off.
+  pub fn put_str(&mut self, x: usize, y: usize, text: &str) {
+    if y >= self.height() {
+      return;
+    }
+    for (i, byte) in text.bytes().enumerate() {
+      if x + i >= self.width() {
+        break;
+      }
+      self.ids[(x + i, y)] = byte;
+    }
+  }
+
+  /// Writes the text centered on the row given.
+  pub fn put_str_centered(&mut self, y: usize, text: &str) {
+    let x = self.width().saturating_sub(text.len()) / 2;
+    self.put_str(x, y, text);
+  }
+
+  /// Converts the screen into lines of text, top row first.
+  ///
+  /// With `ansi` set, each cell also gets 24-bit color escape codes.
+  pub fn to_text(&self, ansi: bool) -> String {
+    let mut out = String::with_capacity((self.width() + 1) * self.height());
+    for y in (0..self.height()).rev() {
+      for x in 0..self.width() {
+        if ansi {
+          let (fr, fg, fb) = color_channels(self.fgs[(x, y)]);
+          let (br, bg, bb) = color_channels(self.bgs[(x, y)]);
+          out.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m", fr, fg, fb, br, bg, bb));
+        }
+        out.push(glyph_char(self.ids[(x, y)]));
+      }
+      if ansi {
+        out.push_str("\x1b[0m");
+      }
+      out.push('\n');
+    }
+    out
+  }
+}
+
+/// Splits a color into its red, green, and blue parts.
+pub fn color_channels(color: u32) -> (u8, u8, u8) {
+  const RED: u32 = rgba32!(255, 0, 0, 0);
+  const GREEN: u32 = rgba32!(0, 255, 0, 0);
+  const BLUE: u32 = rgba32!(0, 0, 255, 0);
+  let channel = |mask: u32| ((color & mask) >> mask.trailing_zeros()) as u8;
+  (channel(RED), channel(GREEN), channel(BLUE))
+}
+
+/// The closest plain text character to one of our tileset glyphs.
+pub fn glyph_char(glyph: u8) -> char {
+  match glyph {
+    0 => ' ',
+    WALL_TILE => '#',
+    POTION_GLYPH => '!',
+    BOMB_GLYPH => '*',
+    32..=126 => glyph as char,
+    _ => '?',
+  }
+}
+
+/// How many rows at the top of the screen are kept for the status line.
+pub const STATUS_HEIGHT: usize = 1;
+
+/// How many rows of messages are shown above the status line, if the screen
+/// is tall enough.
+pub const MESSAGE_PANEL_HEIGHT: usize = 4;
+
+pub const MESSAGE_GRAY: u32 = rgb32!(200, 200, 200);
+
+/// How a kind of terrain looks.
+fn terrain_glyph(terrain: Terrain) -> (u8, u32) {
+  match terrain {
+    Terrain::Wall => (WALL_TILE, rgb32!(155, 75, 0)),
+    Terrain::Ice => (WALL_TILE, rgb32!(112, 146, 190)),
+    Terrain::Floor => (b'.', rgb32!(128, 128, 128)),
+    Terrain::StairsDown => (b'>', rgb32!(230, 230, 230)),
+    Terrain::StairsUp => (b'<', rgb32!(230, 230, 230)),
+  }
+}
+
+/// A darker version of a color, for things that the player remembers but
+/// can't see right now.
+pub fn dimmed(color: u32) -> u32 {
+  let (r, g, b) = color_channels(color);
+  rgb32!(r / 3, g / 3, b / 3)
+}
+
+/// Draws the map around the player into the bottom `map_height` rows.
+fn draw_map(screen: &mut Screen, game: &GameWorld, map_height: usize) {
+  let offset = game.player_location - Location {
+    x: (screen.width() / 2) as i32,
+    y: (screen.height() / 2) as i32,
+  };
+  let map_view_end = (screen.width(), map_height);
+  for scr_y in 0..map_view_end.1 {
+    for scr_x in 0..map_view_end.0 {
+      let loc_for_this_screen_position = Location {
+        x: scr_x as i32,
+        y: scr_y as i32,
+      } + offset;
+      let (glyph, color) = if game.visible.contains(&loc_for_this_screen_position) && game.terrain.in_bounds(loc_for_this_screen_position) {
+        match game.creatures.at(loc_for_this_screen_position) {
+          Some(creature_here) => (creature_here.icon, creature_here.color),
+          None => match game
+            .item_locations
+            .get(&loc_for_this_screen_position)
+            .and_then(|item_vec_ref| item_vec_ref.get(0))
+          {
+            Some(&item) => {
+              let def = game.item_registry.def(item);
+              (def.glyph, def.color)
+            }
+            None => terrain_glyph(game.terrain.get(loc_for_this_screen_position)),
+          },
+        }
+      } else {
+        match game.map_memory.get(loc_for_this_screen_position) {
+          Some(RememberedTile { item: Some(item), .. }) => {
+            let def = game.item_registry.def(item);
+            (def.glyph, dimmed(def.color))
+          }
+          Some(RememberedTile { terrain, item: None }) => {
+            let (glyph, color) = terrain_glyph(terrain);
+            (glyph, dimmed(color))
+          }
+          None => (b' ', 0),
+        }
+      };
+      screen.ids[(scr_x, scr_y)] = glyph;
+      screen.fgs[(scr_x, scr_y)] = color;
+    }
+  }
+}
+
+/// Draws the map, with the message panel and the status bar above it.
+///
+/// `message_scroll` is how many of the newest messages to skip, for looking
+/// back through the log.
+pub fn draw_game(screen: &mut Screen, game: &GameWorld, message_scroll: usize) {
+  screen.clear();
+  let panel_height = MESSAGE_PANEL_HEIGHT.min(screen.height().saturating_sub(STATUS_HEIGHT + 1));
+  draw_map(screen, game, screen.height() - STATUS_HEIGHT - panel_height);
+  // draw the messages, newest at the bottom.
+  for (i, event) in game.messages.recent(message_scroll, panel_height).enumerate() {
+    let y = screen.height() - panel_height + i;
+    for x in 0..screen.width() {
+      screen.fgs[(x, y)] = MESSAGE_GRAY;
+    }
+    screen.put_str(0, y, &event.to_string());
+  }
+  // draw the status bar.
+  let status_y = screen.height() - STATUS_HEIGHT - panel_height;
+  for x in 0..screen.width() {
+    screen.fgs[(x, status_y)] = KINDA_LIME_GREEN;
+  }
+  let player = game.creatures.player().unwrap();
+  let mut status_line = format!("Depth: {}, HP: {}, Enemies: {}", game.depth, player.hit_points, game.creatures.len() - 1);
+  for effect in player.statuses.iter() {
+    status_line.push_str(&format!(", {} ({})", effect.kind, effect.turns_left));
+  }
+  if message_scroll > 0 {
+    status_line.push_str(&format!(" [{} back]", message_scroll));
+  }
+  screen.put_str(0, status_y, &status_line);
+}
+
+/// The player's inventory, grouped up and in letter order.
+pub fn cataloged_inventory(game: &GameWorld) -> Vec<(Item, usize)> {
+  let mut map_item_count = BTreeMap::new();
+  for item_ref in game.creatures.player().unwrap().inventory.iter() {
+    *map_item_count.entry(*item_ref).or_insert(0) += 1;
+  }
+  map_item_count.into_iter().collect()
+}
+
+pub fn draw_inventory(screen: &mut Screen, game: &GameWorld) {
+  screen.clear();
+
+  let mut item_list = vec![];
+  for (key, val) in cataloged_inventory(game).into_iter() {
+    let name = &game.item_registry.def(key).name;
+    match val {
+      0 => panic!("what the heck?"),
+      1 => item_list.push(name.clone()),
+      count => item_list.push(format!("{} ({})", name, count)),
+    }
+  }
+
+  // draw the menu title
+  let top_y = screen.height() - 1;
+  screen.put_str_centered(top_y, "== Inventory ==");
+  // draw the items
+  let mut the_y_position: isize = screen.height() as isize - 2;
+  if !item_list.is_empty() {
+    for (i, item) in item_list.into_iter().enumerate() {
+      if the_y_position < 0 {
+        break;
+      }
+      let letter = i + ('a' as u8 as usize);
+      screen.put_str(0, the_y_position as usize, &format!("{}) {}", letter as u8 as char, item));
+      the_y_position -= 1;
+    }
+  } else {
+    the_y_position -= 1;
+    if the_y_position >= 0 {
+      screen.put_str_centered(the_y_position as usize, "You have no items on hand.");
+    }
+    the_y_position -= 1;
+  }
+  // then what's being worn, numbered by slot
+  the_y_position -= 1;
+  if the_y_position >= 0 {
+    screen.put_str_centered(the_y_position as usize, "== Equipment ==");
+  }
+  let equipment = &game.creatures.player().unwrap().equipment;
+  for (i, slot) in EquipSlot::ALL.iter().enumerate() {
+    the_y_position -= 1;
+    if the_y_position < 0 {
+      break;
+    }
+    let worn = equipment.get(slot).map(|&item| game.item_registry.def(item).name.as_str()).unwrap_or("-");
+    screen.put_str(0, the_y_position as usize, &format!("{}) {}: {}", i + 1, slot, worn));
+  }
+}
+
+/// The background behind the path that a thrown item would take.
+pub const THROW_PATH_COLOR: u32 = rgb32!(80, 60, 0);
+
+/// The background behind where a thrown item would stop, if that's short of
+/// the target.
+pub const THROW_IMPACT_COLOR: u32 = rgb32!(150, 30, 30);
+
+pub fn draw_targeting(screen: &mut Screen, game: &GameWorld, delta: Location) {
+  screen.clear();
+  draw_map(screen, game, screen.height() - STATUS_HEIGHT);
+
+  // draw the menu title
+  let top_y = screen.height() - 1;
+  screen.put_str_centered(top_y, "== Select A Target ==");
+
+  // the player is in the middle of the map, so that's where everything is
+  // measured from.
+  let center = Location {
+    x: (screen.width() / 2) as i32,
+    y: (screen.height() / 2) as i32,
+  };
+  let (width, map_height) = (screen.width(), screen.height() - STATUS_HEIGHT);
+  let screen_pos = |delta: Location| {
+    let Location { x, y } = center + delta;
+    if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < map_height {
+      Some((x as usize, y as usize))
+    } else {
+      None
+    }
+  };
+
+  // shade where the item would fly, and where it would stop
+  let target = game.player_location + delta;
+  let trajectory = game.trace_projectile(game.player_location, target);
+  for &location in trajectory.path.iter() {
+    if let Some(pos) = screen_pos(location - game.player_location) {
+      screen.bgs[pos] = THROW_PATH_COLOR;
+    }
+  }
+  if trajectory.impact != target {
+    if let Some(pos) = screen_pos(trajectory.impact - game.player_location) {
+      screen.bgs[pos] = THROW_IMPACT_COLOR;
+    }
+  }
+
+  // highlight the target
+  if let Some(pos) = screen_pos(delta) {
+    const FULL_ALPHA: u32 = rgba32!(0, 0, 0, 255);
+    screen.fgs[pos] = !screen.fgs[pos] | FULL_ALPHA;
+    screen.bgs[pos] = !screen.bgs[pos] | FULL_ALPHA;
+  }
+}
+
+/// How many columns wide the panel that describes what's being looked at
+/// is.
+pub const LOOK_PANEL_WIDTH: usize = 28;
+
+/// Draws the map with a cursor on it, and a panel describing whatever is
+/// under the cursor. The panel goes on whichever side the cursor isn't.
+pub fn draw_look(screen: &mut Screen, game: &GameWorld, delta: Location) {
+  screen.clear();
+  draw_map(screen, game, screen.height() - STATUS_HEIGHT);
+
+  // draw the menu title
+  let top_y = screen.height() - 1;
+  screen.put_str_centered(top_y, "== Look ==");
+
+  // highlight the cursor
+  let cursor = Location {
+    x: (screen.width() / 2) as i32,
+    y: (screen.height() / 2) as i32,
+  } + delta;
+  let (cx, cy) = (cursor.x, cursor.y);
+  if cx >= 0 && cy >= 0 && (cx as usize) < screen.width() && (cy as usize) < screen.height() - STATUS_HEIGHT {
+    const FULL_ALPHA: u32 = rgba32!(0, 0, 0, 255);
+    let pos = (cx as usize, cy as usize);
+    screen.fgs[pos] = !screen.fgs[pos] | FULL_ALPHA;
+    screen.bgs[pos] = !screen.bgs[pos] | FULL_ALPHA;
+  }
+
+  // then the panel, blanking out the map under it
+  let panel_width = LOOK_PANEL_WIDTH.min(screen.width());
+  let panel_x = if cx >= (screen.width() / 2) as i32 { 0 } else { screen.width() - panel_width };
+  let lines = game
+    .describe_location(game.player_location + delta)
+    .map(|description| description.lines())
+    .unwrap_or_else(|| vec!["You don't know what's there.".to_string()]);
+  for y in 0..top_y {
+    for x in panel_x..(panel_x + panel_width) {
+      screen.ids[(x, y)] = 0;
+      screen.fgs[(x, y)] = MESSAGE_GRAY;
+      screen.bgs[(x, y)] = rgb32!(0, 0, 0);
+    }
+  }
+  for (i, line) in lines.iter().enumerate() {
+    if i >= top_y {
+      break;
+    }
+    let shown: String = line.chars().take(panel_width - 1).collect();
+    screen.put_str(panel_x + 1, top_y - 1 - i, &shown);
+  }
+}
+
+/// Draws the death screen.
+pub fn draw_game_over(screen: &mut Screen, game: &GameWorld) {
+  screen.clear();
+  let mut lines = vec!["== You Have Died ==".to_string(), String::new()];
+  if let Some(game_over) = game.game_over() {
+    lines.push(format!("{} on depth {}.", game_over.cause, game_over.depth));
+    lines.push(format!("You lasted {} turns.", game_over.turn));
+  }
+  lines.push(format!("You killed {} monsters.", game.total_kills()));
+  lines.push(String::new());
+  lines.push(format!("Seed: {}", game.seed));
+  let top_y = screen.height() - 1;
+  for (i, line) in lines.iter().enumerate() {
+    if i > top_y {
+      break;
+    }
+    screen.put_str_centered(top_y - i, line);
+  }
+}
+
+#[test]
+fn test_glyph_text() {
+  let mut screen = Screen::new(4, 2);
+  screen.put_str(0, 1, "ab");
+  screen.ids[(3, 0)] = WALL_TILE;
+  assert_eq!(screen.to_text(false), "ab  \n   #\n");
+}
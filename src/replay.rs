@@ -0,0 +1,291 @@
+//! Recording and replaying of games.
+//!
+//! A game is fully determined by the seed it started with and the actions
+//! that the player took, so that's all an `ActionLog` keeps (plus a state
+//! hash after each action, so that a replay can tell exactly when it went
+//! off the rails).
+//!
+//! The log is written as plain text with one entry per line, which makes it
+//! easy to attach to a bug report or to cut down by hand.
+
+use super::*;
+
+use std::io::{self, BufRead, Write};
+
+/// The first line of every action log.
+pub const REPLAY_HEADER: &str = "kasidin-replay 6";
+
+/// Something that the player did which might have changed the world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerAction {
+  Move(Location),
+  Rest,
+  UseItem(char),
+  UseTargetedItem(char, Location),
+  UseStairs,
+  Unequip(EquipSlot),
+}
+
+impl PlayerAction {
+  /// Performs this action within the world given.
+  pub fn apply_to(self, world: &mut GameWorld) {
+    match self {
+      PlayerAction::Move(delta) => world.move_player(delta),
+      PlayerAction::Rest => world.rest(),
+      PlayerAction::UseItem(letter) => drop(world.use_item(letter)),
+      PlayerAction::UseTargetedItem(letter, delta) => drop(world.use_targeted_item(letter, delta)),
+      PlayerAction::UseStairs => drop(world.use_stairs()),
+      PlayerAction::Unequip(slot) => drop(world.unequip(slot)),
+    }
+  }
+}
+
+impl ::std::fmt::Display for PlayerAction {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    match self {
+      PlayerAction::Move(delta) => write!(f, "move {} {}", delta.x, delta.y),
+      PlayerAction::Rest => write!(f, "rest"),
+      PlayerAction::UseItem(letter) => write!(f, "use {}", letter),
+      PlayerAction::UseTargetedItem(letter, delta) => write!(f, "target {} {} {}", letter, delta.x, delta.y),
+      PlayerAction::UseStairs => write!(f, "stairs"),
+      PlayerAction::Unequip(slot) => write!(f, "unequip {}", slot),
+    }
+  }
+}
+
+/// One recorded player action and the world's state hash right after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoggedTurn {
+  pub action: PlayerAction,
+  pub state_hash: u64,
+}
+
+/// Everything needed to play a game back from the start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionLog {
+  pub seed: u64,
+  /// How far the player could see, which depends on the screen that the
+  /// game was played on.
+  pub fov_range: i32,
+  pub turns: Vec<LoggedTurn>,
+}
+
+impl ActionLog {
+  /// Makes a new world along with an empty log to record it.
+  pub fn new_game(seed: u64) -> (Self, GameWorld) {
+    let world = GameWorld::new(seed);
+    let log = ActionLog {
+      seed,
+      fov_range: world.fov_range,
+      turns: vec![],
+    };
+    (log, world)
+  }
+
+  /// Records an action that has just been performed in the world given.
+  pub fn record(&mut self, action: PlayerAction, world: &GameWorld) {
+    self.turns.push(LoggedTurn {
+      action,
+      state_hash: world.state_hash(),
+    });
+  }
+
+  pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+    writeln!(w, "{}", REPLAY_HEADER)?;
+    writeln!(w, "seed {}", self.seed)?;
+    writeln!(w, "fov {}", self.fov_range)?;
+    for turn in self.turns.iter() {
+      writeln!(w, "{} {:016x}", turn.action, turn.state_hash)?;
+    }
+    w.flush()
+  }
+
+  pub fn read_from<R: BufRead>(r: R) -> io::Result<Self> {
+    let bad_line = |line_number: usize, line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: can't parse {:?}", line_number, line));
+    let mut lines = r.lines();
+    match lines.next() {
+      Some(Ok(ref line)) if line == REPLAY_HEADER => {}
+      Some(Err(e)) => return Err(e),
+      _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "not an action log")),
+    }
+    let mut seed = None;
+    let mut fov_range = None;
+    let mut turns = vec![];
+    for (i, line_result) in lines.enumerate() {
+      let line_number = i + 2;
+      let line = line_result?;
+      let words: Vec<&str> = line.split_whitespace().collect();
+      let num = |index: usize| words.get(index).and_then(|word| word.parse::<i32>().ok()).ok_or_else(|| bad_line(line_number, &line));
+      let letter = |index: usize| match words.get(index).map(|word| word.chars().collect::<Vec<char>>()) {
+        Some(ref chars) if chars.len() == 1 => Ok(chars[0]),
+        _ => Err(bad_line(line_number, &line)),
+      };
+      let hash = |index: usize| words.get(index).and_then(|word| u64::from_str_radix(word, 16).ok()).ok_or_else(|| bad_line(line_number, &line));
+      match words.get(0) {
+        None => continue,
+        Some(&"seed") => seed = words.get(1).and_then(|word| word.parse().ok()),
+        Some(&"fov") => fov_range = Some(num(1)?),
+        Some(&"move") => turns.push(LoggedTurn {
+          action: PlayerAction::Move(Location { x: num(1)?, y: num(2)? }),
+          state_hash: hash(3)?,
+        }),
+        Some(&"rest") => turns.push(LoggedTurn {
+          action: PlayerAction::Rest,
+          state_hash: hash(1)?,
+        }),
+        Some(&"use") => turns.push(LoggedTurn {
+          action: PlayerAction::UseItem(letter(1)?),
+          state_hash: hash(2)?,
+        }),
+        Some(&"target") => turns.push(LoggedTurn {
+          action: PlayerAction::UseTargetedItem(letter(1)?, Location { x: num(2)?, y: num(3)? }),
+          state_hash: hash(4)?,
+        }),
+        Some(&"stairs") => turns.push(LoggedTurn {
+          action: PlayerAction::UseStairs,
+          state_hash: hash(1)?,
+        }),
+        Some(&"unequip") => turns.push(LoggedTurn {
+          action: PlayerAction::Unequip(words.get(1).and_then(|word| word.parse().ok()).ok_or_else(|| bad_line(line_number, &line))?),
+          state_hash: hash(2)?,
+        }),
+        Some(_) => return Err(bad_line(line_number, &line)),
+      }
+    }
+    match (seed, fov_range) {
+      (Some(seed), Some(fov_range)) => Ok(ActionLog { seed, fov_range, turns }),
+      (None, _) => Err(io::Error::new(io::ErrorKind::InvalidData, "action log is missing its seed")),
+      (_, None) => Err(io::Error::new(io::ErrorKind::InvalidData, "action log is missing its fov range")),
+    }
+  }
+}
+
+/// A replay went differently from how it was recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayMismatch {
+  /// Index of the first turn that didn't match.
+  pub turn: usize,
+  pub action: PlayerAction,
+  pub expected_hash: u64,
+  pub found_hash: u64,
+}
+
+impl ::std::fmt::Display for ReplayMismatch {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    write!(
+      f,
+      "turn {} ({}) gave state {:016x}, but {:016x} was recorded",
+      self.turn, self.action, self.found_hash, self.expected_hash
+    )
+  }
+}
+
+/// Rebuilds a world from an `ActionLog`, one turn at a time.
+#[derive(Debug)]
+pub struct Replayer {
+  pub log: ActionLog,
+  pub world: GameWorld,
+  next_turn: usize,
+}
+
+impl Replayer {
+  pub fn new(log: ActionLog) -> Self {
+    let mut world = GameWorld::new(log.seed);
+    world.set_fov_range(log.fov_range);
+    Replayer { log, world, next_turn: 0 }
+  }
+
+  /// The number of turns played back so far.
+  pub fn turns_played(&self) -> usize {
+    self.next_turn
+  }
+
+  /// Plays the next turn, giving `Ok(false)` once the log is used up.
+  pub fn step(&mut self) -> Result<bool, ReplayMismatch> {
+    match self.log.turns.get(self.next_turn).cloned() {
+      None => Ok(false),
+      Some(logged) => {
+        logged.action.apply_to(&mut self.world);
+        let found_hash = self.world.state_hash();
+        if found_hash != logged.state_hash {
+          Err(ReplayMismatch {
+            turn: self.next_turn,
+            action: logged.action,
+            expected_hash: logged.state_hash,
+            found_hash,
+          })
+        } else {
+          self.next_turn += 1;
+          Ok(true)
+        }
+      }
+    }
+  }
+
+  /// Plays every remaining turn in the log.
+  pub fn run_to_end(&mut self) -> Result<(), ReplayMismatch> {
+    while self.step()? {}
+    Ok(())
+  }
+}
+
+impl GameWorld {
+  /// A hash of the complete world state.
+  ///
+  /// This is an FNV-1a hash of the save file bytes, so it doesn't depend on
+  /// the hasher that std happens to use this week.
+  pub fn state_hash(&self) -> u64 {
+    struct Fnv1a(u64);
+    impl Write for Fnv1a {
+      fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf.iter() {
+          self.0 ^= byte as u64;
+          self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+        Ok(buf.len())
+      }
+      fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+      }
+    }
+    let mut hasher = Fnv1a(0xcbf29ce484222325);
+    self.save_to(&mut hasher).expect("hashing can't fail");
+    hasher.0
+  }
+}
+
+#[test]
+fn test_replay_matches_recording() {
+  let (mut log, mut world) = ActionLog::new_game(0xBADC0DE);
+  let moves = [Location { x: 1, y: 0 }, Location { x: 0, y: -1 }, Location { x: -1, y: 0 }, Location { x: 0, y: 1 }];
+  for &delta in moves.iter().cycle().take(20) {
+    let action = PlayerAction::Move(delta);
+    action.apply_to(&mut world);
+    log.record(action, &world);
+  }
+
+  let mut text = vec![];
+  log.write_to(&mut text).unwrap();
+  let read_back = ActionLog::read_from(&text[..]).unwrap();
+  assert_eq!(log, read_back);
+
+  let mut replayer = Replayer::new(read_back);
+  assert_eq!(replayer.run_to_end(), Ok(()));
+  assert_eq!(replayer.turns_played(), 20);
+
+  // a tampered hash should be caught on exactly that turn
+  let mut tampered = log.clone();
+  tampered.turns[5].state_hash ^= 1;
+  let mut replayer = Replayer::new(tampered);
+  assert_eq!(replayer.run_to_end().map_err(|mismatch| mismatch.turn), Err(5));
+}
+
+#[test]
+fn test_bad_targets_are_mismatches() {
+  // an edited log can name items that aren't there, or can't be thrown
+  let text = format!("{}\nseed 77\nfov 33\ntarget z 0 0 0000000000000000\n", REPLAY_HEADER);
+  let log = ActionLog::read_from(text.as_bytes()).unwrap();
+  let mut replayer = Replayer::new(log);
+  assert_eq!(replayer.run_to_end().map_err(|mismatch| mismatch.turn), Err(0));
+  assert_eq!(replayer.world.turn_count(), 0);
+}
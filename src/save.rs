@@ -0,0 +1,726 @@
+//! Saving and loading of a `GameWorld`.
+//!
+//! The format is a simple little-endian binary dump. It starts with a magic
+//! tag and a version number, and we refuse to load anything with a version
+//! that we don't know about. Any time the layout below changes the version
+//! should be bumped.
+//!
+//! HashMap iteration order isn't stable, so all of the location keyed data is
+//! written out sorted by location. That way the same world always gives the
+//! exact same bytes.
+
+use super::*;
+
+use std::io::{self, Read, Write};
+
+/// The magic bytes at the start of every save file.
+pub const SAVE_MAGIC: [u8; 4] = *b"KSDN";
+
+/// The version of the save format written by this build.
+pub const SAVE_VERSION: u32 = 17;
+
+/// The longest collection (or string) that we'll read in. Anything longer
+/// means the file is broken, and trying to make room for it could take down
+/// the whole process.
+pub const MAX_SAVED_LEN: usize = 1 << 24;
+
+/// The most tiles that a saved level can have.
+pub const MAX_MAP_TILES: usize = 1 << 20;
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u8<W: Write>(w: &mut W, val: u8) -> io::Result<()> {
+  w.write_all(&[val])
+}
+
+fn write_u32<W: Write>(w: &mut W, val: u32) -> io::Result<()> {
+  w.write_all(&[val as u8, (val >> 8) as u8, (val >> 16) as u8, (val >> 24) as u8])
+}
+
+fn write_i32<W: Write>(w: &mut W, val: i32) -> io::Result<()> {
+  write_u32(w, val as u32)
+}
+
+fn write_u64<W: Write>(w: &mut W, val: u64) -> io::Result<()> {
+  write_u32(w, val as u32)?;
+  write_u32(w, (val >> 32) as u32)
+}
+
+fn write_bool<W: Write>(w: &mut W, val: bool) -> io::Result<()> {
+  write_u8(w, val as u8)
+}
+
+fn write_location<W: Write>(w: &mut W, loc: Location) -> io::Result<()> {
+  write_i32(w, loc.x)?;
+  write_i32(w, loc.y)
+}
+
+fn write_len<W: Write>(w: &mut W, len: usize) -> io::Result<()> {
+  if len > ::std::u32::MAX as usize {
+    Err(invalid_data(format!("collection too large to save: {}", len)))
+  } else {
+    write_u32(w, len as u32)
+  }
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+  let mut buf = [0u8; 1];
+  r.read_exact(&mut buf)?;
+  Ok(buf[0])
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+  let mut buf = [0u8; 4];
+  r.read_exact(&mut buf)?;
+  Ok(buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24)
+}
+
+fn read_i32<R: Read>(r: &mut R) -> io::Result<i32> {
+  read_u32(r).map(|u| u as i32)
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+  let low = read_u32(r)? as u64;
+  let high = read_u32(r)? as u64;
+  Ok(low | high << 32)
+}
+
+fn read_bool<R: Read>(r: &mut R) -> io::Result<bool> {
+  match read_u8(r)? {
+    0 => Ok(false),
+    1 => Ok(true),
+    other => Err(invalid_data(format!("bad bool value: {}", other))),
+  }
+}
+
+fn read_location<R: Read>(r: &mut R) -> io::Result<Location> {
+  let x = read_i32(r)?;
+  let y = read_i32(r)?;
+  Ok(Location { x, y })
+}
+
+fn read_len<R: Read>(r: &mut R) -> io::Result<usize> {
+  let len = read_u32(r)? as usize;
+  if len > MAX_SAVED_LEN {
+    Err(invalid_data(format!("collection too large to load: {}", len)))
+  } else {
+    Ok(len)
+  }
+}
+
+fn write_str<W: Write>(w: &mut W, text: &str) -> io::Result<()> {
+  write_len(w, text.len())?;
+  w.write_all(text.as_bytes())
+}
+
+fn read_str<R: Read>(r: &mut R) -> io::Result<String> {
+  let len = read_len(r)?;
+  // the buffer only grows as bytes actually show up, so a cut off file can't
+  // make us allocate the whole claimed length.
+  let mut bytes = Vec::new();
+  r.take(len as u64).read_to_end(&mut bytes)?;
+  if bytes.len() != len {
+    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "a string got cut off"));
+  }
+  String::from_utf8(bytes).map_err(|_| invalid_data("a string isn't utf8".to_string()))
+}
+
+fn write_creature_id<W: Write>(w: &mut W, id: CreatureID) -> io::Result<()> {
+  write_u32(w, id.index)?;
+  write_u32(w, id.generation)
+}
+
+fn read_creature_id<R: Read>(r: &mut R) -> io::Result<CreatureID> {
+  let index = read_u32(r)?;
+  let generation = read_u32(r)?;
+  Ok(CreatureID { index, generation })
+}
+
+fn write_creature_ids<W: Write>(w: &mut W, ids: &CreatureIdAllocator) -> io::Result<()> {
+  write_len(w, ids.generations.len())?;
+  for (&generation, &live) in ids.generations.iter().zip(ids.live.iter()) {
+    write_u32(w, generation)?;
+    write_bool(w, live)?;
+  }
+  write_len(w, ids.free_list.len())?;
+  for &index in ids.free_list.iter() {
+    write_u32(w, index)?;
+  }
+  Ok(())
+}
+
+fn read_creature_ids<R: Read>(r: &mut R) -> io::Result<CreatureIdAllocator> {
+  let mut out = CreatureIdAllocator::new();
+  for _ in 0..read_len(r)? {
+    out.generations.push(read_u32(r)?);
+    out.live.push(read_bool(r)?);
+  }
+  for _ in 0..read_len(r)? {
+    let index = read_u32(r)?;
+    if index as usize >= out.live.len() || out.live[index as usize] {
+      return Err(invalid_data(format!("bad free creature index: {}", index)));
+    }
+    out.free_list.push(index);
+  }
+  Ok(out)
+}
+
+/// Items are saved by name, so that adding new kinds of item doesn't break
+/// old saves.
+fn write_item<W: Write>(w: &mut W, item: Item, items: &ItemRegistry) -> io::Result<()> {
+  write_str(w, &items.def(item).name)
+}
+
+fn read_item<R: Read>(r: &mut R, items: &ItemRegistry) -> io::Result<Item> {
+  let name = read_str(r)?;
+  items.by_name(&name).ok_or_else(|| invalid_data(format!("unknown item {:?}", name)))
+}
+
+fn write_items<W: Write>(w: &mut W, list: &[Item], items: &ItemRegistry) -> io::Result<()> {
+  write_len(w, list.len())?;
+  for &item in list.iter() {
+    write_item(w, item, items)?;
+  }
+  Ok(())
+}
+
+fn read_items<R: Read>(r: &mut R, items: &ItemRegistry) -> io::Result<Vec<Item>> {
+  let count = read_len(r)?;
+  let mut out = Vec::new();
+  for _ in 0..count {
+    out.push(read_item(r, items)?);
+  }
+  Ok(out)
+}
+
+fn write_equipment<W: Write>(w: &mut W, equipment: &BTreeMap<EquipSlot, Item>, items: &ItemRegistry) -> io::Result<()> {
+  write_len(w, equipment.len())?;
+  for (&slot, &item) in equipment.iter() {
+    write_u8(
+      w,
+      match slot {
+        EquipSlot::Weapon => 0,
+        EquipSlot::Armor => 1,
+        EquipSlot::Shield => 2,
+        EquipSlot::Ring => 3,
+      },
+    )?;
+    write_item(w, item, items)?;
+  }
+  Ok(())
+}
+
+fn read_equipment<R: Read>(r: &mut R, items: &ItemRegistry) -> io::Result<BTreeMap<EquipSlot, Item>> {
+  let mut out = BTreeMap::new();
+  for _ in 0..read_len(r)? {
+    let slot = match read_u8(r)? {
+      0 => EquipSlot::Weapon,
+      1 => EquipSlot::Armor,
+      2 => EquipSlot::Shield,
+      3 => EquipSlot::Ring,
+      other => return Err(invalid_data(format!("bad equipment slot code: {}", other))),
+    };
+    out.insert(slot, read_item(r, items)?);
+  }
+  Ok(out)
+}
+
+fn write_statuses<W: Write>(w: &mut W, statuses: &[StatusEffect]) -> io::Result<()> {
+  write_len(w, statuses.len())?;
+  for effect in statuses.iter() {
+    write_u8(
+      w,
+      match effect.kind {
+        StatusKind::Frozen => 0,
+        StatusKind::Poisoned => 1,
+        StatusKind::Strengthened => 2,
+        StatusKind::Hasted => 3,
+      },
+    )?;
+    write_u32(w, effect.turns_left)?;
+    write_i32(w, effect.potency)?;
+  }
+  Ok(())
+}
+
+fn read_statuses<R: Read>(r: &mut R) -> io::Result<Vec<StatusEffect>> {
+  let mut out = Vec::new();
+  for _ in 0..read_len(r)? {
+    let kind = match read_u8(r)? {
+      0 => StatusKind::Frozen,
+      1 => StatusKind::Poisoned,
+      2 => StatusKind::Strengthened,
+      3 => StatusKind::Hasted,
+      other => return Err(invalid_data(format!("bad status code: {}", other))),
+    };
+    let turns_left = read_u32(r)?;
+    let potency = read_i32(r)?;
+    out.push(StatusEffect { kind, turns_left, potency });
+  }
+  Ok(out)
+}
+
+fn write_terrain<W: Write>(w: &mut W, terrain: Terrain) -> io::Result<()> {
+  write_u8(
+    w,
+    match terrain {
+      Terrain::Wall => 0,
+      Terrain::Floor => 1,
+      Terrain::Ice => 2,
+      Terrain::StairsDown => 3,
+      Terrain::StairsUp => 4,
+    },
+  )
+}
+
+fn read_terrain<R: Read>(r: &mut R) -> io::Result<Terrain> {
+  match read_u8(r)? {
+    0 => Ok(Terrain::Wall),
+    1 => Ok(Terrain::Floor),
+    2 => Ok(Terrain::Ice),
+    3 => Ok(Terrain::StairsDown),
+    4 => Ok(Terrain::StairsUp),
+    other => Err(invalid_data(format!("bad terrain code: {}", other))),
+  }
+}
+
+fn write_ai_kind<W: Write>(w: &mut W, ai: AiKind) -> io::Result<()> {
+  write_u8(
+    w,
+    match ai {
+      AiKind::Chaser => 0,
+      AiKind::Wanderer => 1,
+      AiKind::Coward => 2,
+      AiKind::Kiter => 3,
+      AiKind::Pack => 4,
+    },
+  )
+}
+
+fn read_ai_kind<R: Read>(r: &mut R) -> io::Result<AiKind> {
+  match read_u8(r)? {
+    0 => Ok(AiKind::Chaser),
+    1 => Ok(AiKind::Wanderer),
+    2 => Ok(AiKind::Coward),
+    3 => Ok(AiKind::Kiter),
+    4 => Ok(AiKind::Pack),
+    other => Err(invalid_data(format!("bad ai code: {}", other))),
+  }
+}
+
+fn write_memory<W: Write>(w: &mut W, memory: Option<PlayerMemory>) -> io::Result<()> {
+  match memory {
+    None => write_bool(w, false),
+    Some(memory) => {
+      write_bool(w, true)?;
+      write_location(w, memory.last_seen)?;
+      write_u32(w, memory.turns_since)?;
+      write_bool(w, memory.searching)
+    }
+  }
+}
+
+fn read_memory<R: Read>(r: &mut R) -> io::Result<Option<PlayerMemory>> {
+  if read_bool(r)? {
+    let last_seen = read_location(r)?;
+    let turns_since = read_u32(r)?;
+    let searching = read_bool(r)?;
+    Ok(Some(PlayerMemory {
+      last_seen,
+      turns_since,
+      searching,
+    }))
+  } else {
+    Ok(None)
+  }
+}
+
+fn write_maybe_location<W: Write>(w: &mut W, loc: Option<Location>) -> io::Result<()> {
+  match loc {
+    None => write_bool(w, false),
+    Some(loc) => {
+      write_bool(w, true)?;
+      write_location(w, loc)
+    }
+  }
+}
+
+fn read_maybe_location<R: Read>(r: &mut R) -> io::Result<Option<Location>> {
+  if read_bool(r)? {
+    read_location(r).map(Some)
+  } else {
+    Ok(None)
+  }
+}
+
+fn write_creature<W: Write>(w: &mut W, creature: &Creature, items: &ItemRegistry) -> io::Result<()> {
+  write_creature_id(w, creature.id)?;
+  write_str(w, &creature.name)?;
+  write_u8(w, creature.icon)?;
+  write_u32(w, creature.color)?;
+  write_bool(w, creature.is_the_player)?;
+  write_i32(w, creature.hit_points)?;
+  write_i32(w, creature.max_hit_points)?;
+  write_i32(w, creature.attack_step)?;
+  write_i32(w, creature.damage_step)?;
+  write_i32(w, creature.defense)?;
+  write_i32(w, creature.armor)?;
+  write_i32(w, creature.sight)?;
+  write_i32(w, creature.speed)?;
+  write_i32(w, creature.energy)?;
+  write_ai_kind(w, creature.ai)?;
+  write_memory(w, creature.memory)?;
+  write_u32(w, creature.memory_span)?;
+  write_maybe_location(w, creature.investigating)?;
+  write_bool(w, creature.hurt_by_player)?;
+  write_items(w, &creature.inventory, items)?;
+  write_equipment(w, &creature.equipment, items)?;
+  write_statuses(w, &creature.statuses)
+}
+
+fn read_creature<R: Read>(r: &mut R, items: &ItemRegistry) -> io::Result<Creature> {
+  let id = read_creature_id(r)?;
+  let name = read_str(r)?;
+  let icon = read_u8(r)?;
+  let color = read_u32(r)?;
+  let is_the_player = read_bool(r)?;
+  let hit_points = read_i32(r)?;
+  let max_hit_points = read_i32(r)?;
+  let attack_step = read_i32(r)?;
+  let damage_step = read_i32(r)?;
+  let defense = read_i32(r)?;
+  let armor = read_i32(r)?;
+  let sight = read_i32(r)?;
+  let speed = read_i32(r)?;
+  let energy = read_i32(r)?;
+  let ai = read_ai_kind(r)?;
+  let memory = read_memory(r)?;
+  let memory_span = read_u32(r)?;
+  let investigating = read_maybe_location(r)?;
+  let hurt_by_player = read_bool(r)?;
+  let inventory = read_items(r, items)?;
+  let equipment = read_equipment(r, items)?;
+  let statuses = read_statuses(r)?;
+  Ok(Creature {
+    name,
+    icon,
+    color,
+    is_the_player,
+    id,
+    hit_points,
+    max_hit_points,
+    attack_step,
+    damage_step,
+    defense,
+    armor,
+    sight,
+    speed,
+    energy,
+    ai,
+    memory,
+    memory_span,
+    investigating,
+    hurt_by_player,
+    inventory,
+    equipment,
+    statuses,
+  })
+}
+
+/// Gives the keys of a location keyed map in a stable order.
+fn sorted_locations<V>(map: &HashMap<Location, V>) -> Vec<Location> {
+  let mut out: Vec<Location> = map.keys().cloned().collect();
+  out.sort_by_key(|loc| (loc.y, loc.x));
+  out
+}
+
+fn write_game_over<W: Write>(w: &mut W, game_over: &Option<GameOver>) -> io::Result<()> {
+  match game_over {
+    None => write_u8(w, 0),
+    Some(game_over) => {
+      match game_over.cause {
+        DeathCause::Monster(ref name) => {
+          write_u8(w, 1)?;
+          write_str(w, name)?;
+        }
+        DeathCause::OwnItem(ref name) => {
+          write_u8(w, 2)?;
+          write_str(w, name)?;
+        }
+        DeathCause::Poison => write_u8(w, 3)?,
+      }
+      write_u32(w, game_over.depth)?;
+      write_u64(w, game_over.turn)
+    }
+  }
+}
+
+fn read_game_over<R: Read>(r: &mut R) -> io::Result<Option<GameOver>> {
+  let cause = match read_u8(r)? {
+    0 => return Ok(None),
+    1 => DeathCause::Monster(read_str(r)?),
+    2 => DeathCause::OwnItem(read_str(r)?),
+    3 => DeathCause::Poison,
+    other => return Err(invalid_data(format!("bad death code: {}", other))),
+  };
+  let depth = read_u32(r)?;
+  let turn = read_u64(r)?;
+  Ok(Some(GameOver { cause, depth, turn }))
+}
+
+fn write_map_memory<W: Write>(w: &mut W, memory: &MapMemory, items: &ItemRegistry) -> io::Result<()> {
+  write_len(w, memory.len())?;
+  for loc in sorted_locations(&memory.tiles) {
+    let tile = memory.tiles[&loc];
+    write_location(w, loc)?;
+    write_terrain(w, tile.terrain)?;
+    write_bool(w, tile.item.is_some())?;
+    if let Some(item) = tile.item {
+      write_item(w, item, items)?;
+    }
+  }
+  Ok(())
+}
+
+fn read_map_memory<R: Read>(r: &mut R, items: &ItemRegistry) -> io::Result<MapMemory> {
+  let mut out = MapMemory::default();
+  for _ in 0..read_len(r)? {
+    let loc = read_location(r)?;
+    let terrain = read_terrain(r)?;
+    let item = if read_bool(r)? { Some(read_item(r, items)?) } else { None };
+    out.tiles.insert(loc, RememberedTile { terrain, item });
+  }
+  Ok(out)
+}
+
+/// Writes out one level: its terrain, creatures, items, and what the player
+/// remembers of it.
+fn write_level<W: Write>(
+  w: &mut W, terrain: &TerrainMap, creatures: &CreatureStore, item_locations: &HashMap<Location, Vec<Item>>, map_memory: &MapMemory,
+  items: &ItemRegistry,
+) -> io::Result<()> {
+  write_len(w, terrain.width())?;
+  write_len(w, terrain.height())?;
+  for (_loc, tile) in terrain.iter() {
+    write_terrain(w, tile)?;
+  }
+
+  write_creature_ids(w, &creatures.ids)?;
+  write_len(w, creatures.len())?;
+  for (creature_ref, loc) in creatures.iter() {
+    write_creature(w, creature_ref, items)?;
+    write_location(w, loc)?;
+  }
+
+  write_len(w, item_locations.len())?;
+  for loc in sorted_locations(item_locations) {
+    write_location(w, loc)?;
+    write_items(w, &item_locations[&loc], items)?;
+  }
+  write_map_memory(w, map_memory, items)
+}
+
+fn read_level<R: Read>(r: &mut R, items: &ItemRegistry) -> io::Result<Level> {
+  let width = read_len(r)?;
+  let height = read_len(r)?;
+  match width.checked_mul(height) {
+    Some(tiles) if tiles <= MAX_MAP_TILES => {}
+    _ => return Err(invalid_data(format!("level is too big: {}x{}", width, height))),
+  }
+  let mut terrain = TerrainMap::new(width, height, OUT_OF_BOUNDS_TERRAIN);
+  for y in 0..height {
+    for x in 0..width {
+      terrain.set(Location { x: x as i32, y: y as i32 }, read_terrain(r)?);
+    }
+  }
+
+  let mut creatures = CreatureStore::with_ids(read_creature_ids(r)?);
+  for _ in 0..read_len(r)? {
+    let creature = read_creature(r, items)?;
+    let loc = read_location(r)?;
+    if !creatures.ids.is_live(creature.id) || creatures.get(creature.id).is_some() {
+      return Err(invalid_data(format!("creature has a bad ID {:?}", creature.id)));
+    }
+    if creatures.id_at(loc).is_some() {
+      return Err(invalid_data(format!("two creatures at {:?}", loc)));
+    }
+    creatures.put(creature, loc);
+  }
+  if creatures.len() != creatures.ids.live.iter().filter(|&&live| live).count() {
+    return Err(invalid_data("some creature IDs are live without a creature".to_string()));
+  }
+
+  let mut item_locations = HashMap::new();
+  for _ in 0..read_len(r)? {
+    let loc = read_location(r)?;
+    item_locations.insert(loc, read_items(r, items)?);
+  }
+  let map_memory = read_map_memory(r, items)?;
+
+  Ok(Level {
+    terrain,
+    creatures,
+    item_locations,
+    map_memory,
+  })
+}
+
+impl GameWorld {
+  /// Writes the entire world out to the writer given.
+  pub fn save_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+    let w = &mut w;
+    w.write_all(&SAVE_MAGIC)?;
+    write_u32(w, SAVE_VERSION)?;
+    write_u64(w, self.gen.state())?;
+    write_location(w, self.player_location)?;
+    write_u64(w, self.seed)?;
+    write_u64(w, self.tick_count)?;
+    write_len(w, self.kills.len())?;
+    for (name, &count) in self.kills.iter() {
+      write_str(w, name)?;
+      write_u32(w, count)?;
+    }
+    write_game_over(w, &self.game_over)?;
+
+    write_u32(w, self.depth)?;
+    write_level(
+      w,
+      &self.terrain,
+      &self.creatures,
+      &self.item_locations,
+      &self.map_memory,
+      &self.item_registry,
+    )?;
+    write_len(w, self.other_levels.len())?;
+    for (&depth, level) in self.other_levels.iter() {
+      write_u32(w, depth)?;
+      write_level(
+        w,
+        &level.terrain,
+        &level.creatures,
+        &level.item_locations,
+        &level.map_memory,
+        &self.item_registry,
+      )?;
+    }
+    write_i32(w, self.fov_range)?;
+
+    w.flush()
+  }
+
+  /// Reads a world back in from the reader given.
+  pub fn load_from<R: Read>(mut r: R) -> io::Result<Self> {
+    let r = &mut r;
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if magic != SAVE_MAGIC {
+      return Err(invalid_data("not a save file".to_string()));
+    }
+    let version = read_u32(r)?;
+    if version != SAVE_VERSION {
+      return Err(invalid_data(format!("unsupported save version {}, expected {}", version, SAVE_VERSION)));
+    }
+    let gen = PCG32::new(read_u64(r)?);
+    let player_location = read_location(r)?;
+    let seed = read_u64(r)?;
+    let tick_count = read_u64(r)?;
+    let mut kills = BTreeMap::new();
+    for _ in 0..read_len(r)? {
+      let name = read_str(r)?;
+      kills.insert(name, read_u32(r)?);
+    }
+    let game_over = read_game_over(r)?;
+
+    let depth = read_u32(r)?;
+    let item_registry = ItemRegistry::builtin();
+    let current = read_level(r, &item_registry)?;
+    let mut other_levels = BTreeMap::new();
+    for _ in 0..read_len(r)? {
+      let other_depth = read_u32(r)?;
+      if other_depth == depth || other_levels.contains_key(&other_depth) {
+        return Err(invalid_data(format!("depth {} is in the save twice", other_depth)));
+      }
+      other_levels.insert(other_depth, read_level(r, &item_registry)?);
+    }
+    let fov_range = read_i32(r)?;
+
+    let mut out = GameWorld {
+      player_location,
+      creatures: current.creatures,
+      item_locations: current.item_locations,
+      terrain: current.terrain,
+      gen,
+      depth,
+      other_levels,
+      monster_registry: MonsterRegistry::builtin(),
+      item_registry,
+      messages: MessageLog::new(),
+      seed,
+      tick_count,
+      kills,
+      game_over,
+      #[cfg(test)]
+      ai_overrides: HashMap::new(),
+      flow_fields: Default::default(),
+      map_memory: current.map_memory,
+      visible: HashSet::new(),
+      fov_dirty: true,
+      fov_range,
+    };
+    out.update_fov();
+    Ok(out)
+  }
+}
+
+#[test]
+fn test_save_load_round_trip() {
+  let mut world = GameWorld::new(0xC0FFEE);
+  let mut saved = vec![];
+  world.save_to(&mut saved).unwrap();
+  let mut loaded = GameWorld::load_from(&saved[..]).unwrap();
+  let mut resaved = vec![];
+  loaded.save_to(&mut resaved).unwrap();
+  assert_eq!(saved, resaved);
+
+  // both copies should keep going the exact same way
+  for &delta in [Location { x: 1, y: 0 }, Location { x: 0, y: 1 }, Location { x: -1, y: 0 }].iter() {
+    world.move_player(delta);
+    loaded.move_player(delta);
+  }
+  let mut world_bytes = vec![];
+  world.save_to(&mut world_bytes).unwrap();
+  let mut loaded_bytes = vec![];
+  loaded.save_to(&mut loaded_bytes).unwrap();
+  assert_eq!(world_bytes, loaded_bytes);
+}
+
+#[test]
+fn test_load_rejects_bad_version() {
+  let mut saved = vec![];
+  GameWorld::new(7).save_to(&mut saved).unwrap();
+  saved[4] = saved[4].wrapping_add(1);
+  assert!(GameWorld::load_from(&saved[..]).is_err());
+}
+
+#[test]
+fn test_load_rejects_huge_sizes() {
+  let mut saved = vec![];
+  GameWorld::new(8).save_to(&mut saved).unwrap();
+  // the kill list count comes right after the header, the seed, and the tick
+  // count. A new world hasn't killed anything yet.
+  let kills_at = 40;
+  assert_eq!(&saved[kills_at..kills_at + 4], &[0, 0, 0, 0]);
+
+  let mut huge_string = saved[..kills_at].to_vec();
+  huge_string.extend_from_slice(&[1, 0, 0, 0, 0xF0, 0xFF, 0xFF, 0xFF]);
+  assert!(GameWorld::load_from(&huge_string[..]).is_err());
+
+  // then no game over, the depth, and the width and height of the level
+  let mut huge_map = saved.clone();
+  let size_at = kills_at + 4 + 1 + 4;
+  huge_map[size_at..size_at + 8].copy_from_slice(&[0, 0, 1, 0, 0, 0, 1, 0]);
+  let error = GameWorld::load_from(&huge_map[..]).unwrap_err();
+  assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,148 @@
+//! Deciding who gets to act when.
+//!
+//! Time goes by in ticks. Each tick anyone with at least 0 energy gets to
+//! act, paying for it out of their energy, and then every creature gains
+//! energy equal to its speed. With a normal speed of 10 and a normal action cost of 100
+//! that's one action every 10 ticks, so a speed 20 monster acts twice for
+//! each of your moves and a speed 5 monster acts every other move.
+
+use super::*;
+
+/// Energy spent to step onto a new tile (or bump into a wall).
+pub const MOVE_COST: i32 = 100;
+/// Energy spent to attack.
+pub const ATTACK_COST: i32 = 100;
+/// Energy spent to use an item, targeted or not.
+pub const USE_ITEM_COST: i32 = 100;
+/// Energy spent to put on or take off an item.
+pub const EQUIP_COST: i32 = 100;
+/// Energy spent to do nothing for a turn.
+pub const WAIT_COST: i32 = 100;
+/// Energy spent to go up or down the stairs.
+pub const STAIRS_COST: i32 = 100;
+
+/// How many ticks make up one turn. Things that happen "every turn" (such as
+/// status effects wearing off) go off on this schedule.
+pub const TICKS_PER_TURN: u64 = 10;
+
+impl GameWorld {
+  /// How many full turns have gone by.
+  pub fn turn_count(&self) -> u64 {
+    self.tick_count / TICKS_PER_TURN
+  }
+
+  /// Charges the player for an action that cost `player_cost` energy, then
+  /// runs the clock until the player can act again. Every monster gets to act
+  /// whenever it has the energy along the way.
+  pub fn run_world_turn(&mut self, player_cost: i32) {
+    match self.creatures.player_mut() {
+      Some(player_mut) => player_mut.energy -= player_cost,
+      None => return,
+    }
+    // The player may have changed things, so the shared maps start over.
+    self.clear_flow_fields();
+    while self.game_over.is_none() {
+      self.run_tick();
+      match self.creatures.player_mut() {
+        // a frozen player just loses their turns until they thaw out.
+        Some(player_mut) if player_mut.energy >= 0 && player_mut.has_status(StatusKind::Frozen) => player_mut.energy -= WAIT_COST,
+        Some(player_mut) if player_mut.energy < 0 => {}
+        _ => break,
+      }
+    }
+    self.remove_the_dead();
+    self.update_fov();
+  }
+
+  /// Does a single tick of game time.
+  fn run_tick(&mut self) {
+    // Monsters act in ID order so that a turn always goes the same way.
+    let ready_list: Vec<CreatureID> = self
+      .creatures
+      .iter()
+      .filter(|&(creature_ref, _loc)| !creature_ref.is_the_player && creature_ref.energy >= 0)
+      .map(|(creature_ref, _loc)| creature_ref.id)
+      .collect();
+    for creature_id in ready_list.into_iter() {
+      let still_alive = self
+        .creatures
+        .get(creature_id)
+        .map(|creature_ref| creature_ref.hit_points > 0)
+        .unwrap_or(false);
+      if !still_alive {
+        continue;
+      }
+      let frozen = self.creatures.get(creature_id).unwrap().has_status(StatusKind::Frozen);
+      let cost = if frozen { WAIT_COST } else { self.take_monster_turn(creature_id) };
+      self.creatures.get_mut(creature_id).unwrap().energy -= cost;
+      if self.game_over.is_some() {
+        return;
+      }
+    }
+    for creature_mut in self.creatures.iter_mut() {
+      creature_mut.energy += creature_mut.speed.max(1);
+    }
+    self.tick_count += 1;
+    if self.tick_count % TICKS_PER_TURN == 0 {
+      self.end_turn();
+    }
+  }
+
+  /// Anything that happens once per turn, no matter who's acting.
+  fn end_turn(&mut self) {
+    self.tick_statuses();
+  }
+}
+
+#[test]
+fn test_speed_decides_how_often_monsters_act() {
+  let mut world = GameWorld::new(1234);
+  // clear out the level so that only our test monsters are around.
+  let monster_ids: Vec<CreatureID> = world
+    .creatures
+    .iter()
+    .filter(|&(creature, _)| !creature.is_the_player)
+    .map(|(creature, _)| creature.id)
+    .collect();
+  for cid in monster_ids.into_iter() {
+    world.creatures.remove(cid);
+  }
+  let mut spots = vec![];
+  while spots.len() < 2 {
+    let spot = world.pick_random_floor();
+    if world.creatures.id_at(spot).is_none() && !spots.contains(&spot) {
+      spots.push(spot);
+    }
+  }
+  let fast_id = world
+    .creatures
+    .spawn(spots[0], |id| {
+      let mut out = Creature::new(id, b'f', 0);
+      out.speed = 20;
+      out.ai = AiKind::Wanderer;
+      out
+    })
+    .unwrap();
+  let slow_id = world
+    .creatures
+    .spawn(spots[1], |id| {
+      let mut out = Creature::new(id, b's', 0);
+      out.speed = 5;
+      out.ai = AiKind::Wanderer;
+      out
+    })
+    .unwrap();
+
+  // Every action takes the same energy, so the energy that a monster has
+  // spent tells how many times it acted.
+  let actions_of = |world: &GameWorld, cid: CreatureID| {
+    let creature = world.creatures.get(cid).unwrap();
+    (creature.speed as i64 * world.tick_count as i64 - creature.energy as i64) / MOVE_COST as i64
+  };
+  for _ in 0..10 {
+    world.run_world_turn(MOVE_COST);
+  }
+  assert_eq!(world.turn_count(), 10);
+  assert_eq!(actions_of(&world, fast_id), 20);
+  assert_eq!(actions_of(&world, slow_id), 5);
+}
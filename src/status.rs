@@ -0,0 +1,223 @@
+//! Timed effects that a creature can be under, such as poison or haste.
+//!
+//! Effects count down once per turn (see `TICKS_PER_TURN`) and go away when
+//! they run out. Any stat change that an effect makes goes onto the stats
+//! when it starts and comes back off when it ends, same as equipment.
+
+use super::*;
+
+/// A kind of timed effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusKind {
+  /// Can't act at all.
+  Frozen,
+  /// Takes `potency` damage every turn.
+  Poisoned,
+  /// Has `potency` more damage step.
+  Strengthened,
+  /// Has `potency` more speed.
+  Hasted,
+}
+
+impl StatusKind {
+  /// The stat that this kind of effect raises by its potency, if any.
+  pub fn stat(self) -> Option<Stat> {
+    match self {
+      StatusKind::Strengthened => Some(Stat::DamageStep),
+      StatusKind::Hasted => Some(Stat::Speed),
+      StatusKind::Frozen | StatusKind::Poisoned => None,
+    }
+  }
+}
+
+impl ::std::str::FromStr for StatusKind {
+  type Err = ();
+  fn from_str(s: &str) -> Result<Self, ()> {
+    match s {
+      "frozen" => Ok(StatusKind::Frozen),
+      "poisoned" => Ok(StatusKind::Poisoned),
+      "strengthened" => Ok(StatusKind::Strengthened),
+      "hasted" => Ok(StatusKind::Hasted),
+      _ => Err(()),
+    }
+  }
+}
+
+impl ::std::fmt::Display for StatusKind {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    match self {
+      StatusKind::Frozen => write!(f, "frozen"),
+      StatusKind::Poisoned => write!(f, "poisoned"),
+      StatusKind::Strengthened => write!(f, "strengthened"),
+      StatusKind::Hasted => write!(f, "hasted"),
+    }
+  }
+}
+
+/// One timed effect on a creature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatusEffect {
+  pub kind: StatusKind,
+  /// How many more turns it lasts.
+  pub turns_left: u32,
+  /// How strong it is, which means something different for each kind.
+  pub potency: i32,
+}
+
+impl Creature {
+  /// The effect of this kind that the creature is under, if any.
+  pub fn status(&self, kind: StatusKind) -> Option<&StatusEffect> {
+    self.statuses.iter().find(|effect| effect.kind == kind)
+  }
+
+  pub fn has_status(&self, kind: StatusKind) -> bool {
+    self.status(kind).is_some()
+  }
+}
+
+impl GameWorld {
+  /// Puts an effect on a creature. A creature only ever has one effect of each
+  /// kind, so if it's already under this one they stack:
+  ///
+  /// * Frozen: lasts as long as the longer of the two.
+  /// * Poisoned: the potencies add up, and it lasts as long as the longer of
+  ///   the two.
+  /// * Strengthened and Hasted: the turns add up, but the potency stays what
+  ///   it was.
+  pub(crate) fn add_status(&mut self, creature_id: CreatureID, effect: StatusEffect) {
+    let creature_mut = match self.creatures.get_mut(creature_id) {
+      Some(creature_mut) => creature_mut,
+      None => return,
+    };
+    match creature_mut.statuses.iter_mut().find(|old| old.kind == effect.kind) {
+      Some(old) => match effect.kind {
+        StatusKind::Frozen => old.turns_left = old.turns_left.max(effect.turns_left),
+        StatusKind::Poisoned => {
+          old.potency += effect.potency;
+          old.turns_left = old.turns_left.max(effect.turns_left);
+        }
+        StatusKind::Strengthened | StatusKind::Hasted => old.turns_left += effect.turns_left,
+      },
+      None => {
+        if let Some(stat) = effect.kind.stat() {
+          creature_mut.raise_stat(stat, effect.potency);
+        }
+        creature_mut.statuses.push(effect);
+        self.messages.push(GameEvent::StatusStarted {
+          who: Who::of(creature_mut),
+          status: effect.kind,
+        });
+      }
+    }
+  }
+
+  /// Runs every creature's effects for one turn: poison does its damage, and
+  /// then everything counts down, with anything that runs out going away.
+  pub(crate) fn tick_statuses(&mut self) {
+    // everyone goes in ID order, same as the rest of the turn.
+    for creature_mut in self.creatures.iter_mut() {
+      if creature_mut.statuses.is_empty() {
+        continue;
+      }
+      if let Some(poison) = creature_mut.status(StatusKind::Poisoned).cloned() {
+        creature_mut.hit_points -= poison.potency;
+        self.messages.push(GameEvent::PoisonDamage {
+          who: Who::of(creature_mut),
+          damage: poison.potency,
+        });
+      }
+      let mut still_going = vec![];
+      for mut effect in ::std::mem::replace(&mut creature_mut.statuses, vec![]) {
+        effect.turns_left = effect.turns_left.saturating_sub(1);
+        if effect.turns_left > 0 {
+          still_going.push(effect);
+        } else {
+          if let Some(stat) = effect.kind.stat() {
+            creature_mut.raise_stat(stat, -effect.potency);
+          }
+          self.messages.push(GameEvent::StatusEnded {
+            who: Who::of(creature_mut),
+            status: effect.kind,
+          });
+        }
+      }
+      creature_mut.statuses = still_going;
+    }
+    self.check_player_death(|| DeathCause::Poison);
+  }
+}
+
+#[test]
+fn test_statuses_stack_and_wear_off() {
+  let mut world = GameWorld::new(4242);
+  let spot = world.player_location + Location { x: 1, y: 0 };
+  world.terrain.set(spot, Terrain::Floor);
+  if let Some(cid) = world.creatures.id_at(spot) {
+    world.creatures.remove(cid);
+  }
+  let monster_id = world
+    .creatures
+    .spawn(spot, |id| {
+      let mut out = Creature::new(id, b'k', 0);
+      out.hit_points = 50;
+      out.max_hit_points = 50;
+      out
+    })
+    .unwrap();
+  let effect = |kind, turns_left, potency| StatusEffect { kind, turns_left, potency };
+  let base_step = world.creatures.get(monster_id).unwrap().damage_step;
+
+  world.add_status(monster_id, effect(StatusKind::Strengthened, 2, 3));
+  world.add_status(monster_id, effect(StatusKind::Strengthened, 2, 5));
+  world.add_status(monster_id, effect(StatusKind::Poisoned, 3, 2));
+  world.add_status(monster_id, effect(StatusKind::Poisoned, 1, 1));
+  world.add_status(monster_id, effect(StatusKind::Frozen, 2, 0));
+  world.add_status(monster_id, effect(StatusKind::Frozen, 1, 0));
+  {
+    let monster = world.creatures.get(monster_id).unwrap();
+    // a second dose of strength lasts longer, but isn't any stronger
+    assert_eq!(monster.status(StatusKind::Strengthened), Some(&effect(StatusKind::Strengthened, 4, 3)));
+    assert_eq!(monster.damage_step, base_step + 3);
+    assert_eq!(monster.status(StatusKind::Poisoned), Some(&effect(StatusKind::Poisoned, 3, 3)));
+    assert_eq!(monster.status(StatusKind::Frozen), Some(&effect(StatusKind::Frozen, 2, 0)));
+  }
+
+  world.tick_statuses();
+  world.tick_statuses();
+  {
+    let monster = world.creatures.get(monster_id).unwrap();
+    assert_eq!(monster.hit_points, 50 - 3 - 3);
+    assert!(!monster.has_status(StatusKind::Frozen));
+    assert!(monster.has_status(StatusKind::Poisoned));
+  }
+  world.tick_statuses();
+  world.tick_statuses();
+  let monster = world.creatures.get(monster_id).unwrap();
+  assert_eq!(monster.hit_points, 50 - 3 * 3);
+  assert!(monster.statuses.is_empty());
+  assert_eq!(monster.damage_step, base_step);
+}
+
+#[test]
+fn test_ice_freezes_instead_of_killing() {
+  let mut world = GameWorld::new(5150);
+  let spot = world.player_location + Location { x: 2, y: 0 };
+  world.terrain.set(spot, Terrain::Floor);
+  if let Some(cid) = world.creatures.id_at(spot) {
+    world.creatures.remove(cid);
+  }
+  let monster_id = world.creatures.spawn(spot, |id| Creature::new(id, b'k', 0)).unwrap();
+  let ice_bomb = world.item_registry.by_name("Ice Bomb").unwrap();
+  let player_id = world.creatures.player_id().unwrap();
+  world.apply_item_effects(ice_bomb, spot, player_id);
+  assert_eq!(world.terrain.get(spot), Terrain::Floor);
+  let frozen_for = world.creatures.get(monster_id).unwrap().status(StatusKind::Frozen).unwrap().turns_left;
+
+  // a frozen monster only waits, so it stays put until it thaws out
+  for _ in 0..frozen_for - 1 {
+    world.rest();
+    assert_eq!(world.creatures.location_of(monster_id), Some(spot));
+  }
+  world.rest();
+  assert!(!world.creatures.get(monster_id).unwrap().has_status(StatusKind::Frozen));
+}
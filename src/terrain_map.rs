@@ -0,0 +1,107 @@
+//! A dense, fixed size grid of `Terrain`.
+
+use super::*;
+
+/// What you get when you look at a location outside of the map.
+pub const OUT_OF_BOUNDS_TERRAIN: Terrain = Terrain::Wall;
+
+/// The terrain of a whole level.
+///
+/// Locations go from `(0, 0)` up to `(width - 1, height - 1)`. Reading outside
+/// of that always gives `OUT_OF_BOUNDS_TERRAIN`, and writing outside of it
+/// does nothing, so the map can never grow.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TerrainMap {
+  width: usize,
+  height: usize,
+  tiles: Vec<Terrain>,
+}
+
+impl TerrainMap {
+  pub fn new(width: usize, height: usize, fill: Terrain) -> Self {
+    TerrainMap {
+      width,
+      height,
+      tiles: vec![fill; width * height],
+    }
+  }
+
+  /// Turns a cave buffer (where `true` means a wall) into terrain.
+  pub fn from_caves(caves: &VecImage<bool>) -> Self {
+    let mut out = Self::new(caves.width(), caves.height(), Terrain::Wall);
+    for (x, y, tile) in caves.iter() {
+      out.set(Location { x: x as i32, y: y as i32 }, if *tile { Terrain::Wall } else { Terrain::Floor });
+    }
+    out
+  }
+
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  pub fn in_bounds(&self, loc: Location) -> bool {
+    loc.x >= 0 && loc.y >= 0 && (loc.x as usize) < self.width && (loc.y as usize) < self.height
+  }
+
+  fn index_of(&self, loc: Location) -> Option<usize> {
+    if self.in_bounds(loc) {
+      Some(loc.y as usize * self.width + loc.x as usize)
+    } else {
+      None
+    }
+  }
+
+  pub fn get(&self, loc: Location) -> Terrain {
+    self.index_of(loc).map(|i| self.tiles[i]).unwrap_or(OUT_OF_BOUNDS_TERRAIN)
+  }
+
+  /// Changes the terrain at a location. Gives `false` if the location is out
+  /// of bounds, in which case nothing happens.
+  pub fn set(&mut self, loc: Location, terrain: Terrain) -> bool {
+    match self.index_of(loc) {
+      Some(i) => {
+        self.tiles[i] = terrain;
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// If you can't see past this location.
+  pub fn blocks_sight(&self, loc: Location) -> bool {
+    match self.get(loc) {
+      Terrain::Wall | Terrain::Ice => true,
+      Terrain::Floor | Terrain::StairsDown | Terrain::StairsUp => false,
+    }
+  }
+
+  /// Every location in the map along with its terrain, row by row.
+  pub fn iter(&self) -> impl Iterator<Item = (Location, Terrain)> + '_ {
+    let width = self.width;
+    self.tiles.iter().enumerate().map(move |(i, &terrain)| {
+      (
+        Location {
+          x: (i % width) as i32,
+          y: (i / width) as i32,
+        },
+        terrain,
+      )
+    })
+  }
+}
+
+#[test]
+fn test_terrain_map_bounds() {
+  let mut map = TerrainMap::new(3, 2, Terrain::Floor);
+  assert_eq!(map.get(Location { x: 2, y: 1 }), Terrain::Floor);
+  assert_eq!(map.get(Location { x: 3, y: 1 }), OUT_OF_BOUNDS_TERRAIN);
+  assert_eq!(map.get(Location { x: -1, y: 0 }), OUT_OF_BOUNDS_TERRAIN);
+  assert!(map.set(Location { x: 0, y: 1 }, Terrain::Ice));
+  assert!(!map.set(Location { x: 0, y: 2 }, Terrain::Floor));
+  assert_eq!(map.get(Location { x: 0, y: 1 }), Terrain::Ice);
+  assert_eq!(map.iter().filter(|&(_, t)| t == Terrain::Floor).count(), 5);
+}
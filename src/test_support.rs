@@ -0,0 +1,41 @@
+//! Setup that's shared by tests in more than one module.
+
+use super::*;
+
+/// How far out from the player a `test_arena` is cleared.
+pub(crate) const ARENA_RADIUS: i32 = 3;
+
+impl GameWorld {
+  /// A world for tests, with no monsters and bare floor all around the
+  /// player.
+  pub(crate) fn test_arena(seed: u64) -> Self {
+    let mut world = GameWorld::new(seed);
+    let player_id = world.creatures.player_id().unwrap();
+    for cid in world.creatures.ids().into_iter().filter(|&cid| cid != player_id) {
+      world.creatures.remove(cid);
+    }
+    for y in -ARENA_RADIUS..=ARENA_RADIUS {
+      for x in -ARENA_RADIUS..=ARENA_RADIUS {
+        let spot = world.player_location + Location { x, y };
+        world.terrain.set(spot, Terrain::Floor);
+        world.item_locations.remove(&spot);
+      }
+    }
+    world.mark_fov_dirty();
+    world.update_fov();
+    world
+  }
+
+  /// Puts a monster of the kind named at a spot, clearing away whatever
+  /// (and whoever) was there first.
+  pub(crate) fn spawn_named_at(&mut self, name: &str, location: Location) -> CreatureID {
+    let def = self.monster_registry.by_name(name).unwrap_or_else(|| panic!("no monster named {}", name)).clone();
+    if let Some(cid) = self.creatures.id_at(location) {
+      self.creatures.remove(cid);
+    }
+    self.terrain.set(location, Terrain::Floor);
+    self.item_locations.remove(&location);
+    self.mark_fov_dirty();
+    self.spawn_monster(&def, location).unwrap()
+  }
+}